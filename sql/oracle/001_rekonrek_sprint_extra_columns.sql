@@ -0,0 +1,10 @@
+-- Gateway fields written by default on top of the original mapping. Run
+-- before deploying; a column renamed with COLUMN_<FIELD> must be added under
+-- that name, and one disabled with an empty COLUMN_<FIELD> can be left out.
+ALTER TABLE V_BEN_REKONREK_SPRINT ADD (
+    NAMA_JENIS VARCHAR2(200),
+    KODE_BANK VARCHAR2(20),
+    KODE_JENIS_BANK VARCHAR2(20),
+    NAMA_CABANG VARCHAR2(200),
+    KODE_STATUS VARCHAR2(20)
+);
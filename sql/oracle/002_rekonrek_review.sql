@@ -0,0 +1,11 @@
+-- Accounts without a known status, parked for review instead of loaded.
+CREATE TABLE V_BEN_REKONREK_REVIEW (
+    KODE_SATKER VARCHAR2(6),
+    NOREK VARCHAR2(50) NOT NULL,
+    KDSTATUS VARCHAR2(20),
+    NMSTATUS VARCHAR2(200),
+    REASON VARCHAR2(4000),
+    CREATED_DATE TIMESTAMP,
+    MODIFIED_DATE TIMESTAMP,
+    CONSTRAINT UK_REKONREK_REVIEW_NOREK UNIQUE (NOREK)
+);
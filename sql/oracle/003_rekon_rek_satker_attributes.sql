@@ -0,0 +1,7 @@
+-- Per-satker OWNER, KODE_UNIT_TEKNIS and MATA_UANG overrides, read when
+-- SATKER_ATTRIBUTES is on.
+ALTER TABLE V_BEN_REKON_REK_SATKER ADD (
+    OWNER VARCHAR2(100),
+    KODE_UNIT_TEKNIS VARCHAR2(20),
+    MATA_UANG VARCHAR2(3)
+);
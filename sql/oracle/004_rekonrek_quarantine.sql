@@ -0,0 +1,12 @@
+-- Gateway records that could not be loaded, kept for
+-- `gwsprint quarantine list|revalidate|resubmit`.
+CREATE TABLE V_BEN_REKONREK_QUARANTINE (
+    KODE_SATKER VARCHAR2(6) NOT NULL,
+    -- SHA-256 hex of RAW
+    RECORD_HASH VARCHAR2(64) NOT NULL,
+    RAW CLOB NOT NULL,
+    REASON VARCHAR2(4000),
+    RUN_ID VARCHAR2(36),
+    QUARANTINED_AT TIMESTAMP,
+    CONSTRAINT PK_REKONREK_QUARANTINE PRIMARY KEY (KODE_SATKER, RECORD_HASH)
+);
@@ -0,0 +1,6 @@
+-- JSON of the fields normalization changed, keyed by gateway field name.
+-- VARCHAR2 rather than CLOB, so it binds and compares like the other text
+-- columns. Add it under the COLUMN_ORIGINAL_VALUES name if that is set.
+ALTER TABLE V_BEN_REKONREK_SPRINT ADD (
+    ORIGINAL_VALUES VARCHAR2(4000)
+);
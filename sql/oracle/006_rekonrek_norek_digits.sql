@@ -0,0 +1,44 @@
+-- The default NORMALIZE steps include norek_digits, which drops `-`, `.`
+-- and whitespace from NOREK. NOREK is the MERGE key, so rows loaded before
+-- with separators would no longer match and the next run would insert
+-- them again. Run before deploying, after 005; with COLUMN_ORIGINAL_VALUES
+-- renamed, use that name below.
+--
+-- The old NOREK goes to ORIGINAL_VALUES, as the loader records it. Rows
+-- whose normalized NOREK is taken, by another row or by a second spelling
+-- of the same account, are left alone: the last query lists them to be
+-- merged by hand.
+
+UPDATE V_BEN_REKONREK_SPRINT t
+SET ORIGINAL_VALUES = NVL(ORIGINAL_VALUES, JSON_OBJECT('norek' VALUE t.NOREK)),
+    NOREK = REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', ''),
+    MODIFIED_DATE = CURRENT_TIMESTAMP,
+    VERSION = VERSION + 1
+WHERE REGEXP_LIKE(t.NOREK, '[-.[:space:]]')
+  AND REGEXP_LIKE(REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', ''), '^[0-9]+$')
+  AND NOT EXISTS (
+      SELECT 1 FROM V_BEN_REKONREK_SPRINT o
+      WHERE o.ROWID <> t.ROWID
+        AND REGEXP_REPLACE(o.NOREK, '[-.[:space:]]', '') = REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', '')
+  );
+
+UPDATE V_BEN_REKONREK_REVIEW t
+SET NOREK = REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', ''),
+    MODIFIED_DATE = CURRENT_TIMESTAMP
+WHERE REGEXP_LIKE(t.NOREK, '[-.[:space:]]')
+  AND REGEXP_LIKE(REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', ''), '^[0-9]+$')
+  AND NOT EXISTS (
+      SELECT 1 FROM V_BEN_REKONREK_REVIEW o
+      WHERE o.ROWID <> t.ROWID
+        AND REGEXP_REPLACE(o.NOREK, '[-.[:space:]]', '') = REGEXP_REPLACE(t.NOREK, '[-.[:space:]]', '')
+  );
+
+COMMIT;
+
+-- Accounts still stored under more than one spelling
+SELECT REGEXP_REPLACE(NOREK, '[-.[:space:]]', '') AS NORMALIZED_NOREK,
+       LISTAGG(NOREK, ', ') WITHIN GROUP (ORDER BY NOREK) AS NOREKS
+FROM V_BEN_REKONREK_SPRINT
+WHERE REGEXP_LIKE(REGEXP_REPLACE(NOREK, '[-.[:space:]]', ''), '^[0-9]+$')
+GROUP BY REGEXP_REPLACE(NOREK, '[-.[:space:]]', '')
+HAVING COUNT(*) > 1;
@@ -1,55 +1,1055 @@
-use crate::models::RekeningResponse;
-use anyhow::Result;
-use log::{info, error};
-use serde_json;
+use crate::auth::TokenProvider;
+use crate::cassette::{self, CassetteMode, Interaction, RecordedRequest, RecordedResponse};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::gateway_error::GatewayError;
+use crate::ids::KdSatker;
+use crate::logging;
+use crate::models::{RekeningData, RekeningResponse};
+use crate::rate_limit::RateLimiter;
+use crate::source::{self, RekeningSource, StreamSummary};
+use crate::retry::{FetchError, RetryPolicy};
+use crate::streaming::{self, ChunkReader};
+use crate::tls::TlsConfig;
+use crate::config::{env_parse, env_secs};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::{debug, info, error, warn};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::env;
+use std::future::Future;
+use std::io;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+
+/// Body chunks held between the network and the streaming parser.
+const STREAM_CHUNK_BUFFER: usize = 16;
+
+/// How the gateway splits a satker's accounts across requests.
+#[derive(Debug, Clone)]
+pub enum Pagination {
+    /// One request returns every account.
+    Disabled,
+    /// `offset`/`limit` query parameters, offset starting at 0.
+    OffsetLimit { page_size: usize },
+    /// `page`/`size` query parameters, page starting at 1.
+    PageSize { page_size: usize },
+}
+
+impl Pagination {
+    /// Reads PAGINATION_MODE (`none`, `offset` or `page`) and PAGE_SIZE.
+    pub fn from_env() -> Result<Self> {
+        let mode = env::var("PAGINATION_MODE").unwrap_or_else(|_| "none".to_string());
+        let page_size = env_parse::<usize>("PAGE_SIZE")?.unwrap_or(100);
+        if page_size == 0 {
+            return Err(anyhow!("PAGE_SIZE must be greater than 0"));
+        }
+
+        match mode.to_lowercase().as_str() {
+            "none" => Ok(Pagination::Disabled),
+            "offset" => Ok(Pagination::OffsetLimit { page_size }),
+            "page" => Ok(Pagination::PageSize { page_size }),
+            other => Err(anyhow!("Unknown PAGINATION_MODE: {}", other)),
+        }
+    }
+
+    fn page_size(&self) -> Option<usize> {
+        match self {
+            Pagination::Disabled => None,
+            Pagination::OffsetLimit { page_size } | Pagination::PageSize { page_size } => Some(*page_size),
+        }
+    }
+
+    /// Query string suffix for the zero-based page `index`.
+    fn query(&self, index: usize) -> String {
+        match self {
+            Pagination::Disabled => String::new(),
+            Pagination::OffsetLimit { page_size } => {
+                format!("&offset={}&limit={}", index * page_size, page_size)
+            }
+            Pagination::PageSize { page_size } => {
+                format!("&page={}&size={}", index + 1, page_size)
+            }
+        }
+    }
+}
+
+/// What the page loop needs to know about one page of a response.
+trait Page {
+    fn success(&self) -> bool;
+    fn length(&self) -> i32;
+    /// Elements received, whether or not they deserialized.
+    fn received(&self) -> usize;
+    fn gateway_error(&self) -> GatewayError;
+}
+
+impl Page for RekeningResponse {
+    fn success(&self) -> bool {
+        self.success
+    }
+
+    fn length(&self) -> i32 {
+        self.length
+    }
+
+    fn received(&self) -> usize {
+        RekeningResponse::received(self)
+    }
+
+    fn gateway_error(&self) -> GatewayError {
+        GatewayError::from_response(self)
+    }
+}
+
+/// A streamed page, held until the page loop has checked it.
+struct StreamedPage {
+    summary: StreamSummary,
+    records: Vec<RekeningData>,
+}
+
+impl StreamedPage {
+    async fn forward(self, records: &mpsc::Sender<RekeningData>) -> Result<StreamSummary> {
+        for record in self.records {
+            records.send(record).await.map_err(|_| anyhow!("Record consumer stopped"))?;
+        }
+        Ok(self.summary)
+    }
+}
+
+impl From<RekeningResponse> for StreamedPage {
+    fn from(response: RekeningResponse) -> Self {
+        let summary = StreamSummary {
+            success: response.success,
+            message: response.message,
+            code: response.code,
+            length: response.length,
+            records: response.data.len(),
+            rejects: response.rejects,
+        };
+        Self { summary, records: response.data }
+    }
+}
+
+impl Page for StreamedPage {
+    fn success(&self) -> bool {
+        self.summary.success
+    }
+
+    fn length(&self) -> i32 {
+        self.summary.length
+    }
+
+    fn received(&self) -> usize {
+        self.summary.received()
+    }
+
+    fn gateway_error(&self) -> GatewayError {
+        self.summary.gateway_error()
+    }
+}
+
+/// Walks the pages of one satker and checks them against the `length`
+/// the first page reported. Every page goes through `check` before it is
+/// used, so a count mismatch is an error as soon as a page shows it.
+struct Pages<'a> {
+    kd_satker: &'a str,
+    pagination: &'a Pagination,
+    expected: usize,
+    received: usize,
+    /// Zero-based index of the last page checked.
+    index: usize,
+    last_page_len: usize,
+}
+
+impl<'a> Pages<'a> {
+    /// Checks the first page; `None` when the gateway answered it with
+    /// `success: false`, which the caller reports as is.
+    fn start(kd_satker: &'a str, pagination: &'a Pagination, first: &impl Page) -> Result<Option<Self>> {
+        if !first.success() {
+            return Ok(None);
+        }
+        let expected = usize::try_from(first.length())
+            .map_err(|_| anyhow!("Negative length {} for satker {}", first.length(), kd_satker))?;
+
+        let mut pages = Self { kd_satker, pagination, expected, received: 0, index: 0, last_page_len: 0 };
+        pages.check(first)?;
+        Ok(Some(pages))
+    }
+
+    /// Index of the next page to request, or `None` once the records add
+    /// up to `length`.
+    fn next_index(&mut self) -> Result<Option<usize>> {
+        if self.received == self.expected {
+            return Ok(None);
+        }
+        // Without pagination, or once the gateway runs out of records,
+        // nothing more is coming
+        if self.pagination.page_size().is_none() || self.last_page_len == 0 {
+            return Err(self.mismatch());
+        }
+        self.index += 1;
+        Ok(Some(self.index))
+    }
+
+    fn check(&mut self, page: &impl Page) -> Result<()> {
+        if !page.success() {
+            return Err(anyhow::Error::new(page.gateway_error())
+                .context(format!("Gateway failed on page {} for satker {}", self.index + 1, self.kd_satker)));
+        }
+        if let Some(page_size) = self.pagination.page_size().filter(|_| self.index > 0) {
+            info!("Fetched page {} for satker {}: {} records (page size {})",
+                  self.index + 1, self.kd_satker, page.received(), page_size);
+        }
+
+        self.last_page_len = page.received();
+        self.received += page.received();
+        if self.received > self.expected || (self.pagination.page_size().is_none() && self.received != self.expected) {
+            return Err(self.mismatch());
+        }
+        Ok(())
+    }
+
+    fn mismatch(&self) -> anyhow::Error {
+        let detail = format!("gateway reported {} records, received {}", self.expected, self.received);
+        anyhow::Error::new(GatewayError::MalformedPayload { detail })
+            .context(format!("Record count mismatch for satker {}", self.kd_satker))
+    }
+}
+
+/// Which HTTP version to speak to the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http1,
+    /// Let ALPN pick, preferring HTTP/2 when the gateway offers it.
+    Negotiate,
+    /// HTTP/2 without negotiation; only for gateways known to support it.
+    Http2,
+}
+
+impl std::str::FromStr for HttpVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "http1" => Ok(HttpVersion::Http1),
+            "negotiate" => Ok(HttpVersion::Negotiate),
+            "http2" => Ok(HttpVersion::Http2),
+            other => Err(anyhow!("expected http1, negotiate or http2, got {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub url: String,
+    /// Comma separated hosts, domains and CIDRs that bypass the proxy.
+    pub no_proxy: Option<String>,
+}
+
+/// Everything `ApiClient` needs apart from credentials. Build with
+/// [`ApiClientConfig::builder`] or [`ApiClientConfig::from_env`].
+#[derive(Debug, Clone)]
+pub struct ApiClientConfig {
+    pub gateway_url: String,
+    pub retry_policy: RetryPolicy,
+    pub pagination: Pagination,
+    pub tls: TlsConfig,
+    pub connect_timeout: Option<Duration>,
+    /// Longest wait for the next chunk of a response body.
+    pub read_timeout: Option<Duration>,
+    /// Cap on a whole request, from sending to the last body byte.
+    pub request_timeout: Option<Duration>,
+    /// Without one, the HTTP(S)_PROXY and NO_PROXY environment applies.
+    pub proxy: Option<ProxyConfig>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub http_version: HttpVersion,
+    pub rate_limit: Option<RateLimit>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub cassette: CassetteMode,
+    /// Parse `data` while the body downloads instead of buffering it first.
+    pub streaming: bool,
+    /// Responses larger than this are abandoned, streaming or not.
+    pub max_response_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+/// Client-side quota for gateway requests, shared across concurrent fetches.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+pub struct ApiClientConfigBuilder {
+    config: ApiClientConfig,
+}
+
+impl ApiClientConfig {
+    pub fn builder(gateway_url: impl Into<String>) -> ApiClientConfigBuilder {
+        ApiClientConfigBuilder {
+            config: ApiClientConfig {
+                gateway_url: gateway_url.into(),
+                retry_policy: RetryPolicy::default(),
+                pagination: Pagination::Disabled,
+                tls: TlsConfig::default(),
+                connect_timeout: Some(Duration::from_secs(10)),
+                read_timeout: Some(Duration::from_secs(30)),
+                request_timeout: Some(Duration::from_secs(120)),
+                proxy: None,
+                pool_idle_timeout: Some(Duration::from_secs(90)),
+                pool_max_idle_per_host: None,
+                http_version: HttpVersion::Http1,
+                rate_limit: None,
+                circuit_breaker: None,
+                cassette: CassetteMode::Off,
+                streaming: false,
+                max_response_bytes: Some(64 * 1024 * 1024),
+            },
+        }
+    }
+
+    /// Reads GATEWAY_URL, the HTTP_*/GATEWAY_PROXY settings,
+    /// GATEWAY_RATE_LIMIT_RPS/BURST, CIRCUIT_FAILURE_THRESHOLD/COOLDOWN_SECS,
+    /// GATEWAY_STREAMING and GATEWAY_MAX_RESPONSE_BYTES (0 disables the cap)
+    /// on top of the retry, pagination, TLS and cassette variables.
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::builder(env::var("GATEWAY_URL")?).build();
+        let mut builder = Self::builder(defaults.gateway_url)
+            .retry_policy(RetryPolicy::from_env()?)
+            .pagination(Pagination::from_env()?)
+            .tls(TlsConfig::from_env()?)
+            .cassette(CassetteMode::from_env()?)
+            .connect_timeout(env_secs("HTTP_CONNECT_TIMEOUT_SECS")?.or(defaults.connect_timeout))
+            .read_timeout(env_secs("HTTP_READ_TIMEOUT_SECS")?.or(defaults.read_timeout))
+            .request_timeout(env_secs("HTTP_REQUEST_TIMEOUT_SECS")?.or(defaults.request_timeout))
+            .pool_idle_timeout(env_secs("HTTP_POOL_IDLE_TIMEOUT_SECS")?.or(defaults.pool_idle_timeout))
+            .http_version(env_parse("HTTP_VERSION")?.unwrap_or(defaults.http_version))
+            .streaming(env_parse("GATEWAY_STREAMING")?.unwrap_or(defaults.streaming));
+
+        match env_parse::<usize>("GATEWAY_MAX_RESPONSE_BYTES")? {
+            Some(0) => builder = builder.max_response_bytes(None),
+            Some(max) => builder = builder.max_response_bytes(Some(max)),
+            None => {}
+        }
+
+        if let Some(max) = env_parse::<usize>("HTTP_POOL_MAX_IDLE_PER_HOST")? {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(requests_per_second) = env_parse::<f64>("GATEWAY_RATE_LIMIT_RPS")? {
+            let burst = env_parse::<u32>("GATEWAY_RATE_LIMIT_BURST")?.unwrap_or(1);
+            builder = builder.rate_limit(requests_per_second, burst);
+        }
+        if let Some(failure_threshold) = env_parse::<u32>("CIRCUIT_FAILURE_THRESHOLD")? {
+            let cooldown = env_secs("CIRCUIT_COOLDOWN_SECS")?.unwrap_or(Duration::from_secs(60));
+            builder = builder.circuit_breaker(failure_threshold, cooldown);
+        }
+        if let Ok(url) = env::var("GATEWAY_PROXY") {
+            builder = builder.proxy(url, env::var("GATEWAY_NO_PROXY").ok());
+        }
+
+        Ok(builder.build())
+    }
+
+    fn http_client(&self) -> Result<reqwest::Client> {
+        let offer_h2 = self.http_version != HttpVersion::Http1;
+        let mut builder = self.tls.apply(reqwest::Client::builder(), offer_h2)?;
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(request_timeout) = self.request_timeout {
+            builder = builder.timeout(request_timeout);
+        }
+        builder = builder.pool_idle_timeout(self.pool_idle_timeout);
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        builder = match &self.proxy {
+            Some(proxy) => {
+                let no_proxy = proxy.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string);
+                builder.proxy(
+                    reqwest::Proxy::all(&proxy.url)
+                        .with_context(|| format!("Invalid proxy URL {}", proxy.url))?
+                        .no_proxy(no_proxy),
+                )
+            }
+            // Leave reqwest on HTTP(S)_PROXY and NO_PROXY from the environment
+            None => builder,
+        };
+
+        builder = match self.http_version {
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Negotiate => builder,
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        };
+
+        builder.build().context("Failed to build HTTP client")
+    }
+}
+
+impl ApiClientConfigBuilder {
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn pagination(mut self, pagination: Pagination) -> Self {
+        self.config.pagination = pagination;
+        self
+    }
+
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.config.tls = tls;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+        self.config.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn read_timeout(mut self, read_timeout: Option<Duration>) -> Self {
+        self.config.read_timeout = read_timeout;
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Option<Duration>) -> Self {
+        self.config.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn proxy(mut self, url: impl Into<String>, no_proxy: Option<String>) -> Self {
+        self.config.proxy = Some(ProxyConfig { url: url.into(), no_proxy });
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, pool_idle_timeout: Option<Duration>) -> Self {
+        self.config.pool_idle_timeout = pool_idle_timeout;
+        self
+    }
+
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.config.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn http_version(mut self, http_version: HttpVersion) -> Self {
+        self.config.http_version = http_version;
+        self
+    }
+
+    pub fn rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.config.rate_limit = Some(RateLimit { requests_per_second, burst });
+        self
+    }
+
+    pub fn circuit_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.config.circuit_breaker = Some(CircuitBreakerConfig { failure_threshold, cooldown });
+        self
+    }
+
+    pub fn cassette(mut self, cassette: CassetteMode) -> Self {
+        self.config.cassette = cassette;
+        self
+    }
+
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.config.streaming = streaming;
+        self
+    }
+
+    pub fn max_response_bytes(mut self, max_response_bytes: Option<usize>) -> Self {
+        self.config.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    pub fn build(self) -> ApiClientConfig {
+        self.config
+    }
+}
 
 pub struct ApiClient {
     client: reqwest::Client,
-    gateway_url: String,
-    token: String,
+    config: ApiClientConfig,
+    token_provider: TokenProvider,
+    rate_limiter: Option<RateLimiter>,
+    circuit_breaker: Option<CircuitBreaker>,
 }
 
 impl ApiClient {
-    pub fn new(gateway_url: String, token: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            gateway_url,
-            token,
+    pub fn new(config: ApiClientConfig, token_provider: TokenProvider) -> Result<Self> {
+        let client = config.http_client()?;
+        let rate_limiter = match config.rate_limit {
+            Some(limit) if limit.requests_per_second > 0.0 => {
+                Some(RateLimiter::new(limit.requests_per_second, limit.burst))
+            }
+            Some(_) => return Err(anyhow!("Rate limit must be greater than 0 requests per second")),
+            None => None,
+        };
+        let circuit_breaker = config
+            .circuit_breaker
+            .map(|breaker| CircuitBreaker::new(breaker.failure_threshold, breaker.cooldown));
+
+        Ok(Self {
+            client,
+            config,
+            token_provider,
+            rate_limiter,
+            circuit_breaker,
+        })
+    }
+
+    async fn fetch_page(&self, kd_satker: &str, index: usize) -> Result<RekeningResponse> {
+        self.call_gateway(kd_satker, || self.try_fetch(kd_satker, index)).await
+    }
+
+    async fn stream_page(&self, kd_satker: &str, index: usize) -> Result<StreamedPage> {
+        self.call_gateway(kd_satker, || self.try_stream(kd_satker, index)).await
+    }
+
+    /// Runs one page request through the circuit breaker and retry policy.
+    async fn call_gateway<T, F, Fut>(&self, kd_satker: &str, attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let permit = self.circuit_breaker.as_ref().map(CircuitBreaker::try_acquire).transpose()?;
+
+        let result = self.with_retry(kd_satker, attempt).await;
+
+        // Only exhausted transient errors say the gateway is unhealthy; a 404
+        // or a malformed body still means it answered
+        if let Some(permit) = permit {
+            match &result {
+                Err(FetchError::Retryable { .. }) => permit.failure(),
+                _ => permit.success(),
+            }
         }
+
+        result.map_err(FetchError::into_inner)
     }
 
-    pub async fn fetch_rekening_data(&self, kd_satker: &str) -> Result<RekeningResponse> {
-        let url = format!(
-            "{}/api/v1/sprint/rekening/satker?kdsatker={}",
-            self.gateway_url, kd_satker
-        );
+    async fn with_retry<T, F, Fut>(&self, kd_satker: &str, mut try_once: F) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let mut attempt = 1;
+        let mut token_refreshed = false;
 
-        info!("Fetching data for satker: {} from URL: {}", kd_satker, url);
-        
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
-            .await?;
+        loop {
+            match try_once().await {
+                Ok(value) => return Ok(value),
+                Err(FetchError::Unauthorized(error)) if !token_refreshed => {
+                    if !self.token_provider.invalidate().await {
+                        return Err(FetchError::Unauthorized(error));
+                    }
+                    warn!("Gateway rejected token for satker {}, retrying with a fresh token",
+                          kd_satker);
+                    token_refreshed = true;
+                }
+                Err(FetchError::Retryable { error, retry_after })
+                    if attempt < self.config.retry_policy.max_attempts =>
+                {
+                    let Some(delay) = self.config.retry_policy.delay_for(attempt, retry_after) else {
+                        error!(event = "fetch_failed"; "Giving up on satker {} after {} attempt(s): gateway asked to retry after {:?}, beyond FETCH_BACKOFF_MAX_MS",
+                               kd_satker, attempt, retry_after.unwrap_or_default());
+                        return Err(FetchError::Retryable { error, retry_after });
+                    };
+                    warn!("Attempt {}/{} for satker {} failed: {:?}. Retrying in {:?}",
+                          attempt, self.config.retry_policy.max_attempts, kd_satker, error, delay);
+                    // A replayed response is the same on every attempt, so
+                    // there is nothing to wait for
+                    if !matches!(self.config.cassette, CassetteMode::Replay(_)) {
+                        sleep(delay).await;
+                    }
+                    attempt += 1;
+                }
+                Err(e) => {
+                    error!(event = "fetch_failed"; "Giving up on satker {} after {} attempt(s)", kd_satker, attempt);
+                    return Err(e);
+                }
+            }
+        }
+    }
 
-        let status = response.status();
-        info!("Response status for satker {}: {}", kd_satker, status);
+    fn page_url(&self, kd_satker: &str, index: usize) -> String {
+        format!(
+            "{}/api/v1/sprint/rekening/satker?kdsatker={}{}",
+            self.config.gateway_url, kd_satker, self.config.pagination.query(index)
+        )
+    }
+
+    async fn try_fetch(&self, kd_satker: &str, index: usize) -> Result<RekeningResponse, FetchError> {
+        let url = self.page_url(kd_satker, index);
 
-        let text = response.text().await?;
-        info!("Raw response length for satker {}: {} chars", kd_satker, text.len());
-        info!("Raw response for satker {}: {}", kd_satker, text);
+        let (status, headers, text) = match &self.config.cassette {
+            CassetteMode::Replay(dir) => {
+                info!("Replaying satker {} page {} from cassette", kd_satker, index + 1);
+                let recorded = cassette::replay(dir, kd_satker, index).map_err(FetchError::Permanent)?;
+                let status = recorded.status().map_err(FetchError::Permanent)?;
+                (status, recorded.header_map(), recorded.body)
+            }
+            CassetteMode::Record(dir) => {
+                let (status, headers, text) = self.send_request(kd_satker, &url).await?;
+                let interaction = Interaction {
+                    page: index,
+                    request: RecordedRequest { method: "GET".to_string(), url },
+                    response: RecordedResponse::new(status, &headers, &text),
+                };
+                cassette::record(dir, kd_satker, interaction).map_err(FetchError::Permanent)?;
+                (status, headers, text)
+            }
+            CassetteMode::Off => self.send_request(kd_satker, &url).await?,
+        };
+
+        if !status.is_success() {
+            return Err(Self::status_error(status, &headers, &text, kd_satker));
+        }
+
+        info!(event = "fetch_response"; "Raw response length for satker {}: {} chars", kd_satker, text.len());
+        if logging::raw_bodies_enabled() {
+            debug!(event = "raw_body"; "Raw response for satker {}: {}", kd_satker, text);
+        }
         
         match serde_json::from_str::<RekeningResponse>(&text) {
             Ok(parsed) => {
-                info!("Successfully parsed response for satker {}. Data count: {}", 
-                      kd_satker, parsed.data.len());
+                info!(event = "parsed"; "Successfully parsed response for satker {}. Data count: {}, rejected: {}", 
+                      kd_satker, parsed.data.len(), parsed.rejects.len());
                 Ok(parsed)
             }
-            Err(e) => {
-                error!("Failed to parse response for satker {}: {:?}", kd_satker, e);
-                Err(e.into())
+            Err(e) => Err(Self::parse_error(e, kd_satker)),
+        }
+    }
+
+    /// Streams one page. Cassettes hold whole bodies, so record and replay
+    /// go through the buffered path.
+    async fn try_stream(&self, kd_satker: &str, index: usize) -> Result<StreamedPage, FetchError> {
+        if !matches!(self.config.cassette, CassetteMode::Off) {
+            return self.try_fetch(kd_satker, index).await.map(StreamedPage::from);
+        }
+
+        let url = self.page_url(kd_satker, index);
+        let response = self.send(kd_satker, &url).await?;
+        let status = response.status();
+
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body = self.read_body(response).await?;
+            return Err(Self::status_error(status, &headers, &String::from_utf8_lossy(&body), kd_satker));
+        }
+
+        self.stream_body(kd_satker, response).await
+    }
+
+    /// Feeds the body to a parser on a blocking thread and collects the
+    /// page's records as they are completed.
+    async fn stream_body(&self, kd_satker: &str, response: reqwest::Response) -> Result<StreamedPage, FetchError> {
+        let (chunk_tx, chunk_rx) = mpsc::channel::<io::Result<Bytes>>(STREAM_CHUNK_BUFFER);
+        let (record_tx, mut record_rx) = mpsc::channel(STREAM_CHUNK_BUFFER);
+        let parser = logging::spawn_blocking(move || {
+            streaming::parse_response(ChunkReader::new(chunk_rx), &record_tx)
+        });
+
+        let pump = async {
+            let pumped = self.pump_body(response, &chunk_tx).await;
+            if pumped.is_err() {
+                let _ = chunk_tx.send(Err(io::Error::other("gateway body aborted"))).await;
+            }
+            drop(chunk_tx);
+            pumped
+        };
+        let collect = async {
+            let mut records = Vec::new();
+            while let Some(record) = record_rx.recv().await {
+                records.push(record);
             }
+            records
+        };
+        let (pumped, records) = tokio::join!(pump, collect);
+
+        let parsed = parser
+            .await
+            .map_err(|e| FetchError::Permanent(anyhow!("Streaming parser panicked: {}", e)))?;
+
+        // Nothing of the page has been forwarded yet, so a broken stream is
+        // retried like any other failed request
+        let bytes = pumped?;
+        info!(event = "fetch_response"; "Streamed response length for satker {}: {} bytes", kd_satker, bytes);
+
+        match parsed {
+            Ok(summary) => {
+                info!(event = "parsed"; "Successfully parsed response for satker {}. Data count: {}, rejected: {}",
+                      kd_satker, summary.records, summary.rejects.len());
+                Ok(StreamedPage { summary, records })
+            }
+            Err(e) => Err(Self::parse_error(e, kd_satker)),
+        }
+    }
+
+    /// Copies body chunks to the parser until the body ends or the parser
+    /// stops listening. Returns the number of bytes read.
+    async fn pump_body(
+        &self,
+        mut response: reqwest::Response,
+        chunks: &mpsc::Sender<io::Result<Bytes>>,
+    ) -> Result<usize, FetchError> {
+        let mut total = 0;
+
+        while let Some(chunk) = self.next_chunk(&mut response).await? {
+            total += chunk.len();
+            self.check_size(total)?;
+            if chunks.send(Ok(chunk)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(total)
+    }
+
+    fn status_error(status: StatusCode, headers: &HeaderMap, text: &str, kd_satker: &str) -> FetchError {
+        // Error bodies usually still carry the gateway's message
+        let message = serde_json::from_str::<RekeningResponse>(text)
+            .map(|body| body.message)
+            .unwrap_or_default();
+        let gateway_error = GatewayError::from_status(status, message);
+        FetchError::from_status(status, headers, gateway_error, kd_satker)
+    }
+
+    fn parse_error(e: serde_json::Error, kd_satker: &str) -> FetchError {
+        error!(event = "parse_failed"; "Failed to parse response for satker {}: {:?}", kd_satker, e);
+        FetchError::Permanent(
+            anyhow::Error::new(GatewayError::MalformedPayload { detail: e.to_string() })
+                .context(format!("Failed to parse response for satker {}", kd_satker)),
+        )
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), FetchError> {
+        match self.config.max_response_bytes {
+            Some(max) if size > max => {
+                let detail = format!("response exceeds {} bytes", max);
+                Err(FetchError::Permanent(anyhow::Error::new(GatewayError::MalformedPayload { detail })))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn send_request(
+        &self,
+        kd_satker: &str,
+        url: &str,
+    ) -> Result<(StatusCode, HeaderMap, String), FetchError> {
+        let response = self.send(kd_satker, url).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        let body = self.read_body(response).await?;
+        Ok((status, headers, String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    /// Sends the request and returns once the headers are in.
+    async fn send(&self, kd_satker: &str, url: &str) -> Result<reqwest::Response, FetchError> {
+        info!(event = "fetch"; "Fetching data for satker: {} from URL: {}", kd_satker, url);
+
+        let token = self
+            .token_provider
+            .token(&self.client)
+            .await?;
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
         }
+        
+        let response = self
+            .client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await
+            .map_err(FetchError::from_reqwest)?;
+
+        info!("Response status for satker {}: {}", kd_satker, response.status());
+
+        if let Some(length) = response.content_length() {
+            self.check_size(usize::try_from(length).unwrap_or(usize::MAX))?;
+        }
+
+        Ok(response)
     }
-} 
\ No newline at end of file
+
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>, FetchError> {
+        let mut body = Vec::new();
+
+        while let Some(chunk) = self.next_chunk(&mut response).await? {
+            body.extend_from_slice(&chunk);
+            self.check_size(body.len())?;
+        }
+
+        Ok(body)
+    }
+
+    /// Waits for the next body chunk so a stalled transfer trips
+    /// `read_timeout` instead of holding a fetch slot indefinitely.
+    async fn next_chunk(&self, response: &mut reqwest::Response) -> Result<Option<Bytes>, FetchError> {
+        let chunk = match self.config.read_timeout {
+            Some(read_timeout) => timeout(read_timeout, response.chunk())
+                .await
+                .map_err(|_| FetchError::Retryable {
+                    error: anyhow!("No data from gateway for {:?}", read_timeout),
+                    retry_after: None,
+                })?,
+            None => response.chunk().await,
+        };
+
+        chunk.map_err(FetchError::from_reqwest)
+    }
+}
+
+#[async_trait]
+impl RekeningSource for ApiClient {
+    async fn fetch_rekening_data(&self, kd_satker: &KdSatker) -> Result<RekeningResponse> {
+        let kd_satker = kd_satker.as_str();
+        let mut response = self.fetch_page(kd_satker, 0).await?;
+        let Some(mut pages) = Pages::start(kd_satker, &self.config.pagination, &response)? else {
+            return Ok(response);
+        };
+
+        while let Some(index) = pages.next_index()? {
+            let page = self.fetch_page(kd_satker, index).await?;
+            pages.check(&page)?;
+            response.extend_page(page);
+        }
+
+        Ok(response)
+    }
+
+    /// Each page is parsed as it arrives but only forwarded once it is
+    /// complete and agrees with `length`, so a page that fails the count
+    /// never reaches the sink.
+    async fn stream_rekening_data(
+        &self,
+        kd_satker: &KdSatker,
+        records: mpsc::Sender<RekeningData>,
+    ) -> Result<StreamSummary> {
+        if !self.config.streaming {
+            let response = self.fetch_rekening_data(kd_satker).await?;
+            return source::forward_response(response, &records).await;
+        }
+        let kd_satker = kd_satker.as_str();
+
+        let first = self.stream_page(kd_satker, 0).await?;
+        let Some(mut pages) = Pages::start(kd_satker, &self.config.pagination, &first)? else {
+            return Ok(first.summary);
+        };
+        let mut summary = first.forward(&records).await?;
+
+        while let Some(index) = pages.next_index()? {
+            let page = self.stream_page(kd_satker, index).await?;
+            pages.check(&page)?;
+            summary.extend_page(page.forward(&records).await?);
+        }
+
+        Ok(summary)
+    }
+
+    fn circuit_open_for(&self) -> Option<Duration> {
+        self.circuit_breaker.as_ref().and_then(CircuitBreaker::open_for)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(records: usize, length: i32) -> RekeningResponse {
+        RekeningResponse {
+            success: true,
+            message: String::new(),
+            code: "00".to_string(),
+            data: (0..records).map(|_| RekeningData::default()).collect(),
+            length,
+            rejects: Vec::new(),
+        }
+    }
+
+    /// Answers every request with `body`.
+    async fn gateway(body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await.unwrap();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    fn client(gateway_url: &str, cassette: CassetteMode) -> ApiClient {
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(10),
+        };
+        let config = ApiClientConfig::builder(gateway_url)
+            .retry_policy(retry_policy)
+            .cassette(cassette)
+            .build();
+        ApiClient::new(config, TokenProvider::Static("token".to_string())).unwrap()
+    }
+
+    fn scratch_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("gwsprint-cassettes-{}", uuid::Uuid::new_v4()))
+    }
+
+    const BODY: &str = r#"{"success":true,"message":"ok","code":"00","length":2,"data":[
+        {"kdsatker":"123456","norek":"111"},{"kdsatker":"123456","norek":"222"}]}"#;
+
+    #[tokio::test]
+    async fn recorded_cassette_replays_without_the_gateway() {
+        let dir = scratch_dir();
+        let kd_satker: KdSatker = "123456".parse().unwrap();
+
+        let url = gateway(BODY).await;
+        let recorded = client(&url, CassetteMode::Record(dir.clone()))
+            .fetch_rekening_data(&kd_satker)
+            .await
+            .unwrap();
+
+        // Nothing listens on the discard port, so any request would fail
+        let replayed = client("http://127.0.0.1:9", CassetteMode::Replay(dir.clone()))
+            .fetch_rekening_data(&kd_satker)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let noreks = |response: &RekeningResponse| {
+            response.data.iter().map(|data| data.norek.clone().unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(noreks(&recorded), ["111", "222"]);
+        assert_eq!(noreks(&replayed), noreks(&recorded));
+        assert_eq!(replayed.length, 2);
+    }
+
+    #[tokio::test]
+    async fn satker_missing_from_the_cassettes_is_an_error() {
+        let dir = scratch_dir();
+        let kd_satker: KdSatker = "654321".parse().unwrap();
+
+        let err = client("http://127.0.0.1:9", CassetteMode::Replay(dir))
+            .fetch_rekening_data(&kd_satker)
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("No cassette recorded for satker 654321"), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn replayed_failures_are_retried_without_sleeping() {
+        let dir = scratch_dir();
+        let kd_satker: KdSatker = "123456".parse().unwrap();
+
+        let interaction = Interaction {
+            page: 0,
+            request: RecordedRequest { method: "GET".to_string(), url: String::new() },
+            response: RecordedResponse::new(StatusCode::SERVICE_UNAVAILABLE, &HeaderMap::new(), ""),
+        };
+        cassette::record(&dir, kd_satker.as_str(), interaction).unwrap();
+
+        let start = tokio::time::Instant::now();
+        let result = client("http://127.0.0.1:9", CassetteMode::Replay(dir.clone()))
+            .fetch_rekening_data(&kd_satker)
+            .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+    }
+
+    fn is_mismatch(e: &anyhow::Error) -> bool {
+        matches!(e.downcast_ref::<GatewayError>(), Some(GatewayError::MalformedPayload { .. }))
+    }
+
+    #[test]
+    fn unsuccessful_first_page_is_returned_as_is() {
+        let mut first = page(0, 0);
+        first.success = false;
+        assert!(Pages::start("001", &Pagination::Disabled, &first).unwrap().is_none());
+    }
+
+    #[test]
+    fn single_page_short_of_length_is_an_error() {
+        let err = Pages::start("001", &Pagination::Disabled, &page(2, 3)).err().unwrap();
+        assert!(is_mismatch(&err));
+    }
+
+    #[test]
+    fn single_page_matching_length_is_done() {
+        let mut pages = Pages::start("001", &Pagination::Disabled, &page(3, 3)).unwrap().unwrap();
+        assert_eq!(pages.next_index().unwrap(), None);
+    }
+
+    #[test]
+    fn pages_are_requested_until_length_is_reached() {
+        let pagination = Pagination::OffsetLimit { page_size: 2 };
+        let mut pages = Pages::start("001", &pagination, &page(2, 5)).unwrap().unwrap();
+
+        assert_eq!(pages.next_index().unwrap(), Some(1));
+        pages.check(&page(2, 5)).unwrap();
+        assert_eq!(pages.next_index().unwrap(), Some(2));
+        pages.check(&page(1, 5)).unwrap();
+        assert_eq!(pages.next_index().unwrap(), None);
+    }
+
+    #[test]
+    fn empty_page_before_length_is_an_error() {
+        let pagination = Pagination::PageSize { page_size: 2 };
+        let mut pages = Pages::start("001", &pagination, &page(2, 5)).unwrap().unwrap();
+
+        assert_eq!(pages.next_index().unwrap(), Some(1));
+        pages.check(&page(0, 5)).unwrap();
+        assert!(is_mismatch(&pages.next_index().err().unwrap()));
+    }
+
+    #[test]
+    fn page_overshooting_length_is_rejected_before_it_is_used() {
+        let pagination = Pagination::OffsetLimit { page_size: 2 };
+        let mut pages = Pages::start("001", &pagination, &page(2, 3)).unwrap().unwrap();
+
+        pages.next_index().unwrap();
+        assert!(is_mismatch(&pages.check(&page(2, 3)).err().unwrap()));
+    }
+
+    #[test]
+    fn failed_later_page_is_an_error() {
+        let pagination = Pagination::OffsetLimit { page_size: 2 };
+        let mut pages = Pages::start("001", &pagination, &page(2, 4)).unwrap().unwrap();
+
+        pages.next_index().unwrap();
+        let mut failed = page(0, 0);
+        failed.success = false;
+        assert!(pages.check(&failed).is_err());
+    }
+
+    #[test]
+    fn negative_length_is_an_error() {
+        assert!(Pages::start("001", &Pagination::Disabled, &page(0, -1)).is_err());
+    }
+}
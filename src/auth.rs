@@ -0,0 +1,254 @@
+use crate::config::env_secs;
+use crate::retry::{parse_retry_after, FetchError};
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::env;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+/// Lifetime assumed for tokens that carry neither `expires_in` nor a JWT `exp`.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(300);
+
+/// Source of the bearer token sent to the gateway.
+pub enum TokenProvider {
+    /// Fixed API_TOKEN, never refreshed.
+    Static(String),
+    /// OAuth2 client-credentials grant against a token endpoint.
+    ClientCredentials(ClientCredentials),
+}
+
+pub struct ClientCredentials {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    refresh_margin: Duration,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+struct CachedToken {
+    access_token: String,
+    refresh_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    exp: Option<i64>,
+}
+
+impl TokenProvider {
+    /// Uses the client-credentials grant when OAUTH_TOKEN_URL is set
+    /// (with OAUTH_CLIENT_ID, OAUTH_CLIENT_SECRET, optional OAUTH_SCOPE and
+    /// OAUTH_REFRESH_MARGIN_SECS), otherwise the static API_TOKEN.
+    pub fn from_env() -> Result<Self> {
+        let token_url = match env::var("OAUTH_TOKEN_URL") {
+            Ok(url) => url,
+            Err(_) => return Ok(TokenProvider::Static(env::var("API_TOKEN")?)),
+        };
+
+        let refresh_margin =
+            env_secs("OAUTH_REFRESH_MARGIN_SECS")?.unwrap_or(Duration::from_secs(60));
+
+        Ok(TokenProvider::ClientCredentials(ClientCredentials {
+            token_url,
+            client_id: env::var("OAUTH_CLIENT_ID").context("OAUTH_CLIENT_ID is required")?,
+            client_secret: env::var("OAUTH_CLIENT_SECRET")
+                .context("OAUTH_CLIENT_SECRET is required")?,
+            scope: env::var("OAUTH_SCOPE").ok(),
+            refresh_margin,
+            cached: Mutex::new(None),
+        }))
+    }
+
+    pub async fn token(&self, client: &reqwest::Client) -> Result<String, FetchError> {
+        match self {
+            TokenProvider::Static(token) => Ok(token.clone()),
+            TokenProvider::ClientCredentials(credentials) => credentials.token(client).await,
+        }
+    }
+
+    /// Drops the cached token so the next call fetches a new one. Returns
+    /// false when the provider cannot refresh, i.e. a retry would be pointless.
+    pub async fn invalidate(&self) -> bool {
+        match self {
+            TokenProvider::Static(_) => false,
+            TokenProvider::ClientCredentials(credentials) => {
+                *credentials.cached.lock().await = None;
+                true
+            }
+        }
+    }
+}
+
+impl ClientCredentials {
+    /// Token endpoint failures are classified like gateway ones: a refused
+    /// request, such as `invalid_client`, is permanent, since retrying with
+    /// the same credentials only burns attempts and trips the breaker.
+    async fn token(&self, client: &reqwest::Client) -> Result<String, FetchError> {
+        // Holding the lock across the request keeps concurrent fetches from
+        // all hitting the token endpoint at once
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if Utc::now() < token.refresh_at {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        info!("Requesting access token from {}", self.token_url);
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let response = client
+            .post(&self.token_url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(FetchError::from_reqwest)?;
+        let status = response.status();
+        if !status.is_success() {
+            let error = anyhow!("Token endpoint returned {}", status);
+            if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                return Err(FetchError::Permanent(error));
+            }
+            return Err(FetchError::Retryable { error, retry_after: parse_retry_after(response.headers()) });
+        }
+        let body: TokenResponse = response.json().await.map_err(FetchError::from_reqwest)?;
+
+        let expires_at = match body.expires_in {
+            Some(seconds) => Utc::now() + chrono::Duration::seconds(seconds as i64),
+            None => jwt_expiry(&body.access_token).unwrap_or_else(|| {
+                warn!("Access token has no expiry, assuming {:?}", DEFAULT_TOKEN_LIFETIME);
+                Utc::now() + chrono::Duration::from_std(DEFAULT_TOKEN_LIFETIME).unwrap()
+            }),
+        };
+        let margin = chrono::Duration::from_std(self.refresh_margin)
+            .map_err(|e| FetchError::Permanent(e.into()))?;
+        let refresh_at = expires_at - margin;
+        info!("Access token acquired, refreshing after {}", refresh_at);
+
+        let access_token = body.access_token;
+        *cached = Some(CachedToken {
+            access_token: access_token.clone(),
+            refresh_at,
+        });
+        Ok(access_token)
+    }
+}
+
+/// Reads the `exp` claim from a JWT without verifying it; the gateway does that.
+fn jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let decoded = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: JwtClaims = serde_json::from_slice(&decoded).ok()?;
+    DateTime::from_timestamp(claims.exp?, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `body` to every request and counts them.
+    async fn token_endpoint(body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, hits)
+    }
+
+    fn credentials(token_url: String) -> ClientCredentials {
+        ClientCredentials {
+            token_url,
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            scope: None,
+            refresh_margin: Duration::from_secs(60),
+            cached: Mutex::new(None),
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_cached_until_refresh_is_due() {
+        let (url, hits) = token_endpoint(r#"{"access_token":"abc","expires_in":3600}"#).await;
+        let credentials = credentials(url);
+        let client = reqwest::Client::new();
+
+        assert_eq!(credentials.token(&client).await.unwrap(), "abc");
+        assert_eq!(credentials.token(&client).await.unwrap(), "abc");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn token_inside_refresh_margin_is_fetched_again() {
+        // Expires before the 60 s margin, so it is already due for refresh
+        let (url, hits) = token_endpoint(r#"{"access_token":"abc","expires_in":30}"#).await;
+        let credentials = credentials(url);
+        let client = reqwest::Client::new();
+
+        credentials.token(&client).await.unwrap();
+        credentials.token(&client).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn invalidate_drops_the_cached_token() {
+        let (url, hits) = token_endpoint(r#"{"access_token":"abc","expires_in":3600}"#).await;
+        let provider = TokenProvider::ClientCredentials(credentials(url));
+        let client = reqwest::Client::new();
+
+        provider.token(&client).await.unwrap();
+        assert!(provider.invalidate().await);
+        provider.token(&client).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn static_token_cannot_be_refreshed() {
+        let provider = TokenProvider::Static("fixed".to_string());
+        assert_eq!(provider.token(&reqwest::Client::new()).await.unwrap(), "fixed");
+        assert!(!provider.invalidate().await);
+    }
+
+    #[test]
+    fn jwt_expiry_reads_the_exp_claim() {
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"exp":1700000000}"#);
+        let token = format!("header.{}.signature", payload);
+        assert_eq!(jwt_expiry(&token), DateTime::from_timestamp(1_700_000_000, 0));
+        assert_eq!(jwt_expiry("not-a-jwt"), None);
+    }
+}
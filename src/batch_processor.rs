@@ -1,161 +1,543 @@
-use crate::api_client::ApiClient;
-use crate::db::DatabaseHandler;
-use anyhow::Result;
+use crate::circuit_breaker::CircuitOpen;
+use crate::defaults::{ColumnDefaults, RecordDefaults, SatkerAttributes};
+use crate::gateway_error::GatewayError;
+use crate::ids::KdSatker;
+use crate::logging::{self, SATKER};
+use crate::sink::{RekeningSink, UpsertCounts};
+use crate::models::{RecordReject, Rekening, RekeningData};
+use crate::quarantine::{self, Quarantine, QuarantinedRecord};
+use crate::source::RekeningSource;
+use crate::validation::{RecordRules, ValidationIssue};
+use anyhow::{anyhow, Result};
 use futures::StreamExt;
-use log::{error, info};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use log::{debug, error, info, warn};
+use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
 
-const TRANSACTION_BATCH_SIZE: usize = 50;
+pub const TRANSACTION_BATCH_SIZE: usize = 50;
+/// Records buffered between the source and the sink for one satker.
+const RECORD_CHANNEL_CAPACITY: usize = 2 * TRANSACTION_BATCH_SIZE;
 
-pub struct BatchProcessor {
-    api_client: ApiClient,
-    db_handler: DatabaseHandler,
+/// What the run does when the gateway circuit breaker opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitOpenAction {
+    /// Stop the run; the next scheduled run starts over.
+    Abort,
+    /// Wait out the cooldown, retry the satkers the open circuit turned
+    /// away, then continue with the next chunk.
+    Pause,
 }
 
-impl BatchProcessor {
-    pub fn new(api_client: ApiClient, db_handler: DatabaseHandler) -> Self {
+/// How a single satker ended up in a run.
+#[derive(Debug)]
+pub enum SatkerOutcome {
+    Loaded { counts: UpsertCounts },
+    /// Every account went to review or the quarantine, none was loaded.
+    Quarantined { review: usize, quarantined: usize },
+    /// The gateway answered successfully but the satker has no accounts.
+    NoAccounts,
+    /// The gateway answered but refused or failed the request.
+    GatewayRefused(GatewayError),
+    /// No usable answer from the gateway, e.g. network errors.
+    FetchFailed,
+    /// Not sent to the gateway as its circuit was open.
+    CircuitOpen,
+    /// Accounts were fetched but could not be written.
+    NotWritten,
+}
+
+impl SatkerOutcome {
+    fn label(&self) -> String {
+        match self {
+            SatkerOutcome::Loaded { .. } => "loaded".to_string(),
+            SatkerOutcome::Quarantined { .. } => "quarantined".to_string(),
+            SatkerOutcome::NoAccounts => "no_accounts".to_string(),
+            SatkerOutcome::GatewayRefused(e) => format!("gateway_{}", e.kind()),
+            SatkerOutcome::FetchFailed => "fetch_failed".to_string(),
+            SatkerOutcome::CircuitOpen => "circuit_open".to_string(),
+            SatkerOutcome::NotWritten => "not_written".to_string(),
+        }
+    }
+}
+
+/// What a run did, as logged in its summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Satkers with at least one account written.
+    pub loaded_satkers: usize,
+    pub counts: UpsertCounts,
+    /// Satkers per outcome label.
+    pub outcomes: BTreeMap<String, usize>,
+}
+
+impl std::str::FromStr for CircuitOpenAction {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "abort" => Ok(CircuitOpenAction::Abort),
+            "pause" => Ok(CircuitOpenAction::Pause),
+            other => Err(anyhow!("expected abort or pause, got {}", other)),
+        }
+    }
+}
+
+pub struct BatchProcessor<S: RekeningSource, K: RekeningSink> {
+    source: S,
+    /// Shared with the blocking threads that write each batch.
+    db_handler: Arc<K>,
+    concurrency: usize,
+    on_circuit_open: CircuitOpenAction,
+    rules: RecordRules,
+    defaults: ColumnDefaults,
+    quarantine: Quarantine,
+}
+
+impl<S: RekeningSource, K: RekeningSink> BatchProcessor<S, K> {
+    pub fn new(
+        source: S,
+        db_handler: K,
+        concurrency: usize,
+        on_circuit_open: CircuitOpenAction,
+        rules: RecordRules,
+        defaults: ColumnDefaults,
+        quarantine: Quarantine,
+    ) -> Self {
         Self {
-            api_client,
-            db_handler,
+            source,
+            db_handler: Arc::new(db_handler),
+            concurrency,
+            on_circuit_open,
+            rules,
+            defaults,
+            quarantine,
         }
     }
 
-    pub async fn process_all_satkers(&self) -> Result<()> {
+    pub async fn process_all_satkers(&self) -> Result<RunSummary> {
         let mut processed_satkers = 0;
-        let mut total_records = 0;
+        let mut run_counts = UpsertCounts::default();
+        let mut run_outcomes: BTreeMap<String, usize> = BTreeMap::new();
         let chunk_size = 50;
+        // Each active satker is tried once per run, paging through them by
+        // code. Satkers the open circuit turned away were not tried, and go
+        // again after the pause before the next page
+        let mut after: Option<KdSatker> = None;
+        let mut turned_away: Vec<KdSatker> = Vec::new();
         
         loop {
-            let satkers = self.db_handler.get_active_satkers(chunk_size)?;
-            if satkers.is_empty() {
-                break;
-            }
+            let satkers = if turned_away.is_empty() {
+                let page = self.db_handler.get_active_satkers(after.as_ref(), chunk_size)?;
+                match page.last() {
+                    Some(last) => after = Some(last.clone()),
+                    None => break,
+                }
+                page
+            } else {
+                let retry = turned_away.len().min(chunk_size as usize);
+                turned_away.drain(..retry).collect()
+            };
 
             info!("Processing batch of {} satkers", satkers.len());
             
-            let futures = satkers.iter().cloned().map(|kd_satker| {
-                async move {
-                    self.process_single_satker(&kd_satker).await
-                }
+            let futures = satkers.into_iter().map(|kd_satker| {
+                SATKER.scope(kd_satker.to_string(), async move {
+                    let outcome = self.process_single_satker(&kd_satker).await;
+                    (kd_satker, outcome)
+                })
             });
 
             let results = futures::stream::iter(futures)
-                .buffer_unordered(5)
+                .buffer_unordered(self.concurrency)
                 .collect::<Vec<_>>()
                 .await;
 
             let mut batch_success = 0;
-            let mut batch_records = 0;
+            let mut batch_counts = UpsertCounts::default();
+            let mut batch_outcomes: BTreeMap<String, usize> = BTreeMap::new();
 
-            for result in results {
-                if let Ok((success, records)) = result {
-                    if success {
+            for (kd_satker, result) in results {
+                let outcome = result.unwrap_or_else(|e| {
+                    error!("Satker processing aborted: {:?}", e);
+                    SatkerOutcome::NotWritten
+                });
+                match outcome {
+                    SatkerOutcome::Loaded { counts } => {
                         batch_success += 1;
-                        batch_records += records;
+                        batch_counts += counts;
                     }
+                    SatkerOutcome::CircuitOpen => turned_away.push(kd_satker),
+                    _ => {}
                 }
+                *batch_outcomes.entry(outcome.label()).or_default() += 1;
+            }
+            for (label, count) in &batch_outcomes {
+                *run_outcomes.entry(label.clone()).or_default() += count;
             }
 
             processed_satkers += batch_success;
-            total_records += batch_records;
+            run_counts += batch_counts;
             
-            info!("Batch completed - Successful satkers: {}, Total records inserted: {}", 
-                  batch_success, batch_records);
+            info!(event = "batch_summary", inserted = batch_counts.inserted, updated = batch_counts.updated,
+                  unchanged = batch_counts.unchanged;
+                  "Batch completed - Successful satkers: {}, Inserted: {}, Updated: {}, Unchanged: {}", 
+                  batch_success, batch_counts.inserted, batch_counts.updated, batch_counts.unchanged);
+            info!("Batch outcomes: {:?}", batch_outcomes);
             info!("Progress - Processed satkers: {}, Total records: {}", 
-                  processed_satkers, total_records);
+                  processed_satkers, run_counts.total());
+
+            if let Some(open_for) = self.source.circuit_open_for() {
+                match self.on_circuit_open {
+                    CircuitOpenAction::Abort => {
+                        return Err(anyhow!(
+                            "Gateway circuit open, aborting run after {} satkers", processed_satkers
+                        ));
+                    }
+                    CircuitOpenAction::Pause => {
+                        warn!("Gateway circuit open, pausing run for {:?}", open_for);
+                        sleep(open_for).await;
+                    }
+                }
+            }
 
             sleep(Duration::from_secs(1)).await;
         }
 
-        info!("Completed processing. Total successful satkers: {}, Total records: {}", 
-              processed_satkers, total_records);
-        Ok(())
+        info!(event = "run_summary", inserted = run_counts.inserted, updated = run_counts.updated,
+              unchanged = run_counts.unchanged;
+              "Completed processing. Total successful satkers: {}, Inserted: {}, Updated: {}, Unchanged: {}", 
+              processed_satkers, run_counts.inserted, run_counts.updated, run_counts.unchanged);
+        info!(event = "run_outcomes"; "Satker outcomes for run: {:?}", run_outcomes);
+        Ok(RunSummary {
+            loaded_satkers: processed_satkers,
+            counts: run_counts,
+            outcomes: run_outcomes,
+        })
     }
 
-    async fn process_single_satker(&self, kd_satker: &str) -> Result<(bool, i32)> {
-        info!("Starting to process satker: {}", kd_satker);
-        
-        match self.api_client.fetch_rekening_data(kd_satker).await {
-            Ok(response) => {
-                if !response.success || response.data.is_empty() {
-                    info!("No data for satker: {}", kd_satker);
-                    return Ok((false, 0));
-                }
+    async fn process_single_satker(&self, kd_satker: &KdSatker) -> Result<SatkerOutcome> {
+        info!(event = "satker_start"; "Starting to process satker: {}", kd_satker);
+
+        let attributes = if self.defaults.per_satker() {
+            self.db_handler.satker_attributes(kd_satker)?
+        } else {
+            SatkerAttributes::default()
+        };
+        let defaults = self.defaults.for_satker(attributes);
+
+        let (records_tx, records_rx) = mpsc::channel(RECORD_CHANNEL_CAPACITY);
+        let (fetched, written) = tokio::join!(
+            self.source.stream_rekening_data(kd_satker, records_tx),
+            self.write_records(kd_satker, &defaults, records_rx),
+        );
+
+        let mut written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                error!("Failed to write records for satker {}: {:?}", kd_satker, e);
+                return Ok(SatkerOutcome::NotWritten);
+            }
+        };
 
-                let conn = match self.db_handler.begin_transaction() {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        error!("Failed to begin transaction for satker {}: {:?}", kd_satker, e);
-                        return Ok((false, 0));
+        // Full batches are already committed and stay; the last one is
+        // dropped, and the satker keeps its old fetch date and is loaded
+        // again on the next run
+        let summary = match fetched {
+            Ok(summary) => summary,
+            Err(e) => {
+                return match e.downcast_ref::<GatewayError>() {
+                    Some(gateway_error) => {
+                        error!(event = "gateway_refused", kind = gateway_error.kind();
+                               "Gateway refused satker {}: {:?}", kd_satker, e);
+                        Ok(SatkerOutcome::GatewayRefused(gateway_error.clone()))
+                    }
+                    None if e.downcast_ref::<CircuitOpen>().is_some() => {
+                        warn!(event = "circuit_open"; "Satker {} not fetched: {}", kd_satker, e);
+                        Ok(SatkerOutcome::CircuitOpen)
+                    }
+                    None => {
+                        error!(event = "fetch_failed"; "Failed to fetch data for satker {}: {:?}", kd_satker, e);
+                        Ok(SatkerOutcome::FetchFailed)
                     }
                 };
+            }
+        };
 
-                let mut success_count = 0;
-                let mut error_count = 0;
-                let mut skipped_count = 0;
-                let mut current_batch = 0;
-
-                info!("Starting transaction for satker {} with {} records", 
-                      kd_satker, response.data.len());
-
-                for (idx, data) in response.data.iter().enumerate() {
-                    match data.to_rekening() {
-                        Some(rekening) => {
-                            info!("Processing record {}/{} for satker {}: {}", 
-                                  idx + 1, response.data.len(), kd_satker, rekening.no_rekening);
-                            
-                            match self.db_handler.insert_rekening_batch(&conn, &rekening) {
-                                Ok(_) => {
-                                    info!("Successfully inserted record {}/{}: {}", 
-                                          idx + 1, response.data.len(), rekening.no_rekening);
-                                    success_count += 1;
-                                },
-                                Err(e) => {
-                                    error!("Failed to insert record {}/{} - {}: {:?}", 
-                                           idx + 1, response.data.len(), rekening.no_rekening, e);
-                                    error_count += 1;
-                                    let _ = DatabaseHandler::rollback_transaction(&conn);
-                                    return Ok((false, 0));
-                                }
-                            }
-
-                            current_batch += 1;
-                            if current_batch >= TRANSACTION_BATCH_SIZE {
-                                info!("Committing intermediate batch of {} records", current_batch);
-                                DatabaseHandler::commit_transaction(&conn)?;
-                                conn.execute("SET TRANSACTION READ WRITE", &[])?;
-                                current_batch = 0;
-                            }
-                        },
-                        None => {
-                            skipped_count += 1;
-                            info!("Skipping record {}/{} for satker {} due to invalid/missing NOREK", 
-                                  idx + 1, response.data.len(), kd_satker);
-                        }
-                    }
+        if !summary.success {
+            let gateway_error = summary.gateway_error();
+            error!(event = "gateway_refused", kind = gateway_error.kind();
+                   "Gateway refused satker {}: {}", kd_satker, gateway_error);
+            return Ok(SatkerOutcome::GatewayRefused(gateway_error));
+        }
+        if written.received == 0 {
+            if summary.rejects.is_empty() {
+                info!(event = "no_data"; "No data for satker: {}", kd_satker);
+                return Ok(SatkerOutcome::NoAccounts);
+            }
+            error!("All {} records for satker {} were malformed", summary.rejects.len(), kd_satker);
+        }
+
+        // The rejects go in with the last batch
+        let mut last = std::mem::take(&mut written.pending);
+        for reject in &summary.rejects {
+            last.quarantine.push(self.reject_record(kd_satker, reject));
+        }
+        self.write_batch(kd_satker, last, &mut written).await?;
+
+        let error_count = written.failed_count;
+        let upserts = written.upserts;
+        let quarantined = written.quarantined_count;
+        info!(event = "satker_summary", inserted = upserts.inserted, updated = upserts.updated,
+              unchanged = upserts.unchanged;
+              "Processing summary for satker {} - Inserted: {}, Updated: {}, Unchanged: {}, Errors: {}, Skipped: {}, Rejected: {}, Review: {}, Quarantined: {}", 
+              kd_satker, upserts.inserted, upserts.updated, upserts.unchanged, error_count, written.skipped_count, summary.rejects.len(),
+              written.review_count, quarantined);
+        info!(event = "tglizin_formats"; "tglizin formats for satker {}: {:?}", kd_satker, written.tglizin_formats);
+        if !written.issues.is_empty() {
+            info!(event = "validation_summary"; "Validation issues for satker {}: {:?}", kd_satker, written.issues);
+        }
+
+        if upserts.total() > 0 {
+            self.db_handler.update_last_fetch_date(kd_satker)?;
+            return Ok(SatkerOutcome::Loaded { counts: upserts });
+        }
+        if written.review_count == 0 && quarantined == 0 {
+            error!("No successful inserts for satker {}", kd_satker);
+            return Ok(SatkerOutcome::NotWritten);
+        }
+
+        // The fetch date is stamped all the same: every record is in review
+        // or the quarantine, and fetching the satker again would only store
+        // the same records there until someone resubmits them
+        warn!(event = "satker_quarantined"; "Nothing loaded for satker {}: {} in review, {} quarantined",
+              kd_satker, written.review_count, quarantined);
+        self.db_handler.update_last_fetch_date(kd_satker)?;
+        Ok(SatkerOutcome::Quarantined { review: written.review_count, quarantined })
+    }
+
+    /// Converts records as the source hands them over and writes them in
+    /// batches of TRANSACTION_BATCH_SIZE. The last, partial batch is left
+    /// in `pending` for the caller, to be written once the fetch is known
+    /// to be complete.
+    async fn write_records(
+        &self,
+        kd_satker: &KdSatker,
+        defaults: &RecordDefaults,
+        mut records: mpsc::Receiver<RekeningData>,
+    ) -> Result<Written> {
+        let mut written = Written::default();
+
+        while let Some(data) = records.recv().await {
+            written.received += 1;
+            let idx = written.received;
+            let converted = data.to_rekening(&self.rules, defaults);
+            let issues = match &converted {
+                Ok(rekening) => {
+                    let format = rekening.tgl_izin_format.as_deref().unwrap_or("null");
+                    *written.tglizin_formats.entry(format.to_string()).or_default() += 1;
+                    &rekening.warnings
                 }
+                Err(issues) => issues,
+            };
+            for issue in issues {
+                report_issue(kd_satker, idx, issue);
+                *written.issues.entry(issue.key()).or_default() += 1;
+            }
 
-                // Final commit for remaining records
-                if current_batch > 0 {
-                    DatabaseHandler::commit_transaction(&conn)?;
+            match converted {
+                Ok(rekening) if rekening.status_rekening.is_none() => {
+                    written.pending.review.push((idx, rekening));
+                },
+                Ok(rekening) => {
+                    info!("Processing record {} for satker {}: {}", 
+                          idx, kd_satker, logging::norek(rekening.no_rekening.as_str()));
+                    written.pending.upserts.push((idx, rekening, data));
+                },
+                Err(issues) => {
+                    written.skipped_count += 1;
+                    info!(event = "record_skipped"; "Skipping record {} for satker {} after failed validation", 
+                          idx, kd_satker);
+                    let reason = quarantine::validation_reason(&issues);
+                    written.pending.quarantine.push(QuarantinedRecord::from_data(kd_satker, &data, reason)?);
                 }
+            }
 
-                info!("Processing summary for satker {} - Success: {}, Errors: {}, Skipped: {}", 
-                      kd_satker, success_count, error_count, skipped_count);
+            if written.pending.len() >= TRANSACTION_BATCH_SIZE {
+                info!("Committing intermediate batch of {} records", written.pending.len());
+                let batch = std::mem::take(&mut written.pending);
+                self.write_batch(kd_satker, batch, &mut written).await?;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Writes one batch in its own transaction on a blocking thread. The
+    /// transaction is opened and committed there without awaiting, so no
+    /// lock is held while the satker waits on the gateway.
+    async fn write_batch(&self, kd_satker: &KdSatker, batch: Batch, written: &mut Written) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let sink = Arc::clone(&self.db_handler);
+        let quarantine = self.quarantine.clone();
+        let kd_satker = kd_satker.clone();
 
-                if error_count == 0 && success_count > 0 {
-                    self.db_handler.update_last_fetch_date(kd_satker)?;
-                    Ok((true, success_count))
-                } else {
-                    error!("No successful inserts for satker {}", kd_satker);
-                    let _ = DatabaseHandler::rollback_transaction(&conn);
-                    Ok((false, 0))
+        let counts = logging::spawn_blocking(move || {
+            let tx = sink.begin_transaction()?;
+            match write_batch(&*sink, &quarantine, &kd_satker, &tx, batch) {
+                Ok(counts) => {
+                    sink.commit(&tx)?;
+                    Ok(counts)
+                }
+                Err(e) => {
+                    let _ = sink.rollback(&tx);
+                    Err(e)
                 }
-            },
-            Err(e) => {
-                error!("Failed to fetch data for satker {}: {:?}", kd_satker, e);
-                Ok((false, 0))
             }
+        })
+        .await
+        .map_err(|e| anyhow!("Batch writer panicked: {}", e))??;
+
+        written.upserts += counts.upserts;
+        written.failed_count += counts.failed_count;
+        written.review_count += counts.review_count;
+        written.quarantined_count += counts.quarantined_count;
+        Ok(())
+    }
+
+    fn reject_record(&self, kd_satker: &KdSatker, reject: &RecordReject) -> QuarantinedRecord {
+        error!(event = "record_rejected", index = reject.index;
+               "Rejected malformed record {} for satker {}: {}",
+               reject.index + 1, kd_satker, reject.error);
+        if logging::raw_bodies_enabled() {
+            debug!(event = "raw_body"; "Rejected record {} raw JSON: {}", reject.index + 1, reject.raw);
         }
+        let reason = format!("Malformed record: {}", reject.error);
+        QuarantinedRecord::new(kd_satker, reject.raw.clone(), reason)
     }
-} 
\ No newline at end of file
+}
+
+/// Queues the reviews, stores the quarantined records and upserts the
+/// accounts of one batch in `tx`, quarantining the rows the database
+/// refused.
+fn write_batch<K: RekeningSink>(
+    sink: &K,
+    quarantine: &Quarantine,
+    kd_satker: &KdSatker,
+    tx: &K::Transaction,
+    batch: Batch,
+) -> Result<BatchCounts> {
+    let mut counts = BatchCounts::default();
+
+    for (idx, rekening) in &batch.review {
+        let norek = logging::norek(rekening.no_rekening.as_str());
+        if let Err(e) = sink.queue_for_review(tx, rekening, &rekening.review_reason()) {
+            error!(event = "record_failed"; "Failed to queue record {} - {} for review: {:?}", idx, norek, e);
+            return Err(e);
+        }
+        info!(event = "record_review"; "Queued record {} for review: {}", idx, norek);
+        counts.review_count += 1;
+    }
+
+    for record in &batch.quarantine {
+        quarantine.store(sink, tx, record)?;
+        counts.quarantined_count += 1;
+    }
+
+    if batch.upserts.is_empty() {
+        return Ok(counts);
+    }
+    let (records, rekenings): (Vec<_>, Vec<_>) = batch
+        .upserts
+        .into_iter()
+        .map(|(idx, rekening, data)| ((idx, data), rekening))
+        .unzip();
+    let results = sink.upsert_rekening_batch(tx, &rekenings)?;
+
+    for (result, (rekening, (idx, data))) in results.into_iter().zip(rekenings.iter().zip(records)) {
+        let norek = logging::norek(rekening.no_rekening.as_str());
+        match result {
+            Ok(outcome) => {
+                info!(event = "record_upserted", outcome = outcome.as_str();
+                      "Record {} {}: {}", idx, outcome.as_str(), norek);
+                counts.upserts.record(outcome);
+            }
+            Err(message) => {
+                error!(event = "record_failed"; "Failed to insert record {} - {}: {}", idx, norek, message);
+                counts.failed_count += 1;
+                let record = QuarantinedRecord::from_data(kd_satker, &data, format!("Upsert failed: {}", message))?;
+                quarantine.store(sink, tx, &record)?;
+                counts.quarantined_count += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Records converted since the last write, to go into the database in
+/// one transaction.
+#[derive(Default)]
+struct Batch {
+    /// Accounts to upsert, with their position and the gateway record for
+    /// logs and the quarantine.
+    upserts: Vec<(usize, Rekening, RekeningData)>,
+    /// Accounts without a STATUS_REKENING, parked for review.
+    review: Vec<(usize, Rekening)>,
+    /// Records that failed validation, or came in malformed.
+    quarantine: Vec<QuarantinedRecord>,
+}
+
+impl Batch {
+    fn len(&self) -> usize {
+        self.upserts.len() + self.review.len() + self.quarantine.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// What writing one batch did.
+#[derive(Default)]
+struct BatchCounts {
+    upserts: UpsertCounts,
+    failed_count: usize,
+    review_count: usize,
+    quarantined_count: usize,
+}
+
+/// What `write_records` did with the records it received.
+#[derive(Default)]
+struct Written {
+    /// Records the source handed over.
+    received: usize,
+    /// Converted records not written yet.
+    pending: Batch,
+    /// Upserts that succeeded, by what they did to the row.
+    upserts: UpsertCounts,
+    /// Upserts that failed; the records are quarantined.
+    failed_count: usize,
+    skipped_count: usize,
+    review_count: usize,
+    /// Skipped and failed records written to the quarantine.
+    quarantined_count: usize,
+    /// Records per tglizin format that matched, `null` for NULL dates.
+    tglizin_formats: BTreeMap<String, usize>,
+    /// Validation issues per `field.rule`, warnings included.
+    issues: BTreeMap<String, usize>,
+}
+
+fn report_issue(kd_satker: &KdSatker, idx: usize, issue: &ValidationIssue) {
+    if issue.is_error() {
+        error!(event = "validation_issue", field = issue.field, rule = issue.rule,
+               severity = issue.severity.as_str();
+               "Record {} for satker {}: {}", idx, kd_satker, issue);
+    } else {
+        warn!(event = "validation_issue", field = issue.field, rule = issue.rule,
+              severity = issue.severity.as_str();
+              "Record {} for satker {}: {}", idx, kd_satker, issue);
+    }
+}
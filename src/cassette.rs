@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Record/replay of gateway traffic, one JSON cassette per satker.
+#[derive(Debug, Clone, Default)]
+pub enum CassetteMode {
+    #[default]
+    Off,
+    /// Call the gateway and write every response to the directory.
+    Record(PathBuf),
+    /// Serve responses from the directory without touching the network.
+    Replay(PathBuf),
+}
+
+impl CassetteMode {
+    /// Reads CASSETTE_MODE (`off`, `record` or `replay`) and CASSETTE_DIR.
+    pub fn from_env() -> Result<Self> {
+        let mode = env::var("CASSETTE_MODE").unwrap_or_else(|_| "off".to_string());
+        let dir = || -> Result<PathBuf> {
+            Ok(PathBuf::from(env::var("CASSETTE_DIR").context("CASSETTE_DIR is required")?))
+        };
+
+        match mode.to_lowercase().as_str() {
+            "off" => Ok(CassetteMode::Off),
+            "record" => Ok(CassetteMode::Record(dir()?)),
+            "replay" => Ok(CassetteMode::Replay(dir()?)),
+            other => Err(anyhow!("Unknown CASSETTE_MODE: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub kd_satker: String,
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Interaction {
+    /// Zero-based page index, always 0 without pagination.
+    pub page: usize,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The Authorization header is deliberately not recorded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl RecordedResponse {
+    pub fn new(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        let headers = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value.to_str().ok().map(|value| (name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Self {
+            status: status.as_u16(),
+            headers,
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(&self) -> Result<StatusCode> {
+        StatusCode::from_u16(self.status).context("Invalid status in cassette")
+    }
+
+    pub fn header_map(&self) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (name.parse::<reqwest::header::HeaderName>(), value.parse()) {
+                map.insert(name, value);
+            }
+        }
+        map
+    }
+}
+
+fn cassette_path(dir: &Path, kd_satker: &str) -> PathBuf {
+    dir.join(format!("{}.json", kd_satker))
+}
+
+/// Stores the interaction, replacing an earlier one for the same page so
+/// a retried request keeps only its final response.
+pub fn record(dir: &Path, kd_satker: &str, interaction: Interaction) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Cannot create cassette directory {}", dir.display()))?;
+    let path = cassette_path(dir, kd_satker);
+
+    // Page 0 starts a fresh recording for the satker
+    let mut cassette = if interaction.page == 0 || !path.exists() {
+        Cassette {
+            kd_satker: kd_satker.to_string(),
+            interactions: Vec::new(),
+        }
+    } else {
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid cassette {}", path.display()))?
+    };
+
+    cassette.interactions.retain(|existing| existing.page != interaction.page);
+    cassette.interactions.push(interaction);
+
+    fs::write(&path, serde_json::to_string_pretty(&cassette)?)
+        .with_context(|| format!("Cannot write cassette {}", path.display()))
+}
+
+pub fn replay(dir: &Path, kd_satker: &str, page: usize) -> Result<RecordedResponse> {
+    let path = cassette_path(dir, kd_satker);
+    if !path.exists() {
+        return Err(anyhow!("No cassette recorded for satker {} in {}", kd_satker, dir.display()));
+    }
+
+    let cassette: Cassette = serde_json::from_str(&fs::read_to_string(&path)?)
+        .with_context(|| format!("Invalid cassette {}", path.display()))?;
+
+    cassette
+        .interactions
+        .into_iter()
+        .find(|interaction| interaction.page == page)
+        .map(|interaction| interaction.response)
+        .ok_or_else(|| anyhow!("Cassette for satker {} has no page {}", kd_satker, page + 1))
+}
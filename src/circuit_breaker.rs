@@ -0,0 +1,165 @@
+use log::{info, warn};
+use std::fmt;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Returned instead of calling the gateway while the circuit is open.
+#[derive(Debug)]
+pub struct CircuitOpen {
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gateway circuit is open, next probe in {:?}", self.retry_in)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// Cooldown elapsed and one probe call is on its way.
+    HalfOpen,
+}
+
+/// Opens after `failure_threshold` consecutive gateway failures, rejects
+/// calls for `cooldown`, then lets a single probe decide whether to close.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Asks permission for a call. Fails fast while open, and while a
+    /// half-open probe is still outstanding.
+    pub fn try_acquire(&self) -> Result<Permit<'_>, CircuitOpen> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(Permit { breaker: self, probe: false }),
+            State::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(CircuitOpen { retry_in: until - now });
+                }
+                info!("Gateway circuit half-open, sending probe");
+                *state = State::HalfOpen;
+                Ok(Permit { breaker: self, probe: true })
+            }
+            State::HalfOpen => Err(CircuitOpen { retry_in: self.cooldown }),
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen) {
+            info!("Gateway probe succeeded, closing circuit");
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let open = match *state {
+            State::Closed { failures } if failures + 1 < self.failure_threshold => {
+                *state = State::Closed { failures: failures + 1 };
+                false
+            }
+            State::Open { .. } => false,
+            _ => true,
+        };
+
+        if open {
+            warn!("Opening gateway circuit for {:?}", self.cooldown);
+            *state = State::Open { until: Instant::now() + self.cooldown };
+        }
+    }
+
+    /// A probe that never reported back leaves its outcome unknown; let the
+    /// next call probe again rather than staying half-open for good.
+    fn release_probe(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen) {
+            warn!("Gateway probe abandoned, next call probes again");
+            *state = State::Open { until: Instant::now() };
+        }
+    }
+
+    /// Time until the next probe is allowed, or `None` when calls go through.
+    pub fn open_for(&self) -> Option<Duration> {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => None,
+            State::Open { until } => Some(until.saturating_duration_since(Instant::now())),
+            State::HalfOpen => Some(self.cooldown),
+        }
+    }
+}
+
+/// Permission for one gateway call, to be settled with `success` or
+/// `failure`. Dropping it unsettled, e.g. when the call is cancelled, hands
+/// a half-open probe slot back.
+#[must_use]
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Permit<'_> {
+    pub fn success(mut self) {
+        self.probe = false;
+        self.breaker.record_success();
+    }
+
+    pub fn failure(mut self) {
+        self.probe = false;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.release_probe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.try_acquire().unwrap().failure();
+        breaker
+    }
+
+    #[test]
+    fn probe_blocks_other_calls_until_settled() {
+        let breaker = open_breaker();
+        let probe = breaker.try_acquire().unwrap();
+        assert!(breaker.try_acquire().is_err());
+        probe.success();
+        assert!(breaker.open_for().is_none());
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn dropped_probe_hands_the_slot_back() {
+        let breaker = open_breaker();
+        drop(breaker.try_acquire().unwrap());
+        let probe = breaker.try_acquire().expect("next call may probe again");
+        probe.failure();
+        assert!(breaker.open_for().is_some());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::ids::NoIzin;
+use crate::models::Rekening;
+use chrono::{NaiveDate, NaiveDateTime};
+use anyhow::{anyhow, Result};
+use std::env;
+
+/// A value bound for one column.
+#[derive(Debug, Clone, Copy)]
+pub enum SqlValue<'a> {
+    Text(&'a str),
+    Integer(i64),
+    Date(NaiveDate),
+    Timestamp(NaiveDateTime),
+    /// Typed, as array binds take a column's type from its first row.
+    Null(SqlType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlType {
+    Text,
+    Integer,
+    Date,
+    Timestamp,
+}
+
+pub type Accessor = fn(&Rekening) -> SqlValue<'_>;
+
+/// One column of V_BEN_REKONREK_SPRINT and the `Rekening` field it holds.
+pub struct Column {
+    pub name: String,
+    pub value: Accessor,
+    /// Overwritten when the NOREK already exists; identity columns are not.
+    pub on_update: bool,
+    /// Written for a new NOREK; MODIFIED_* columns are not.
+    pub on_insert: bool,
+}
+
+impl Column {
+    fn new(name: &str, value: Accessor) -> Self {
+        Self { name: name.to_string(), value, on_update: true, on_insert: true }
+    }
+
+    /// Whether a differing value makes an upsert an update; when none of
+    /// these differ the row is left unchanged. Audit columns do not count.
+    pub fn detects_change(&self) -> bool {
+        self.on_update && self.on_insert
+    }
+
+    fn insert_only(mut self) -> Self {
+        self.on_update = false;
+        self
+    }
+
+    fn update_only(mut self) -> Self {
+        self.on_insert = false;
+        self
+    }
+}
+
+/// The account columns written by every upsert, in bind order. Declared
+/// once here and rendered into SQL by each backend.
+pub struct ColumnMap {
+    columns: Vec<Column>,
+}
+
+/// Columns outside the original mapping, written unless disabled: the
+/// extra gateway fields and the pre-normalization values. Variable,
+/// default target column and accessor; sql/oracle/001 and 005 add them to
+/// an existing table.
+const EXTRA_COLUMNS: [(&str, &str, Accessor); 6] = [
+    ("COLUMN_NMJENIS", "NAMA_JENIS", |r| SqlValue::Text(&r.nama_jenis)),
+    ("COLUMN_KDBANK", "KODE_BANK", |r| SqlValue::Text(&r.kode_bank)),
+    ("COLUMN_KDJENBANK", "KODE_JENIS_BANK", |r| SqlValue::Text(&r.kode_jenis_bank)),
+    ("COLUMN_NMCABANG", "NAMA_CABANG", |r| SqlValue::Text(&r.nama_cabang)),
+    ("COLUMN_KDSTATUS", "KODE_STATUS", |r| SqlValue::Text(&r.kode_status)),
+    ("COLUMN_ORIGINAL_VALUES", "ORIGINAL_VALUES", |r| {
+        r.original_values.as_deref().map_or(SqlValue::Null(SqlType::Text), SqlValue::Text)
+    }),
+];
+
+impl ColumnMap {
+    /// The original columns plus `nmjenis`, `kdbank`, `kdjenbank`,
+    /// `nmcabang`, `kdstatus` and the pre-normalization values in
+    /// ORIGINAL_VALUES. COLUMN_<FIELD> renames a target column;
+    /// setting it empty stops writing that field. The full `tglizin`
+    /// timestamp is only written when COLUMN_TGLIZIN_TS names a column.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|key| env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut columns = vec![
+            Column::new("KODE", |r| SqlValue::Text(&r.kdjenis)).insert_only(),
+            Column::new("KODE_SATKER", |r| SqlValue::Text(r.kd_satker.as_str())).insert_only(),
+            Column::new("NAMA_BANK", |r| SqlValue::Text(&r.nama_bank)),
+            Column::new("NAMA_REK", |r| SqlValue::Text(&r.nama_rekening)),
+            Column::new("NO_IZIN", |r| SqlValue::Text(r.no_izin.as_ref().map_or("", NoIzin::as_str))),
+            Column::new("NOREK", |r| SqlValue::Text(r.no_rekening.as_str())).insert_only(),
+            Column::new("TGL_IZIN", |r| r.tgl_izin.map_or(SqlValue::Null(SqlType::Date), SqlValue::Date)),
+            Column::new("OWNER", |r| SqlValue::Text(&r.owner)),
+            Column::new("KODE_UNIT_TEKNIS", |r| SqlValue::Text(&r.kode_unit_teknis)),
+            Column::new("DESC_STATUS_REKENING", |r| SqlValue::Text(&r.desc_status_rekening)),
+            Column::new("STATUS_REKENING", |r| r.status_rekening.map_or(SqlValue::Null(SqlType::Integer), SqlValue::Integer)),
+            Column::new("MATA_UANG", |r| SqlValue::Text(&r.mata_uang)),
+            Column::new("CREATED_BY", |r| SqlValue::Text(&r.audit_user)).insert_only(),
+            Column::new("MODIFIED_BY", |r| SqlValue::Text(&r.audit_user)).update_only(),
+        ];
+
+        for (key, default, value) in EXTRA_COLUMNS {
+            let name = var(key).unwrap_or_else(|| default.to_string());
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            if !is_identifier(name) {
+                return Err(anyhow!("Invalid column name {:?} for {}", name, key));
+            }
+            if columns.iter().any(|c| c.name.eq_ignore_ascii_case(name)) {
+                return Err(anyhow!("{} maps to {}, which is already written", key, name));
+            }
+            columns.push(Column::new(&name.to_uppercase(), value));
+        }
+
+        if let Some(name) = var("COLUMN_TGLIZIN_TS") {
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(anyhow!("Invalid column name {:?} for COLUMN_TGLIZIN_TS", name));
+            }
+            columns.push(Column::new(&name.to_uppercase(), |r| {
+                r.tgl_izin_timestamp.map_or(SqlValue::Null(SqlType::Timestamp), SqlValue::Timestamp)
+            }));
+        }
+
+        Ok(Self { columns })
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn values<'a>(&self, rekening: &'a Rekening) -> Vec<SqlValue<'a>> {
+        self.columns.iter().map(|c| (c.value)(rekening)).collect()
+    }
+}
+
+/// Column names are spliced into SQL, so only plain identifiers pass.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn column_map(vars: &[(&str, &str)]) -> Result<ColumnMap> {
+        let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>();
+        ColumnMap::from_vars(|key| vars.get(key).cloned())
+    }
+
+    fn names(columns: &ColumnMap) -> Vec<&str> {
+        columns.columns().iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn defaults_write_the_original_and_extra_columns() {
+        let columns = column_map(&[]).unwrap();
+        let names = names(&columns);
+        assert_eq!(names.len(), 20);
+        assert_eq!(&names[..2], ["KODE", "KODE_SATKER"]);
+        assert_eq!(&names[14..], ["NAMA_JENIS", "KODE_BANK", "KODE_JENIS_BANK", "NAMA_CABANG", "KODE_STATUS", "ORIGINAL_VALUES"]);
+    }
+
+    #[test]
+    fn extra_column_can_be_renamed_or_dropped() {
+        let columns = column_map(&[("COLUMN_NMCABANG", "cabang"), ("COLUMN_KDSTATUS", " ")]).unwrap();
+        let names = names(&columns);
+        assert!(names.contains(&"CABANG"));
+        assert!(!names.contains(&"NAMA_CABANG"));
+        assert!(!names.contains(&"KODE_STATUS"));
+    }
+
+    #[test]
+    fn timestamp_column_is_only_written_when_named() {
+        assert!(!names(&column_map(&[]).unwrap()).contains(&"TGL_IZIN_TS"));
+        let columns = column_map(&[("COLUMN_TGLIZIN_TS", "tgl_izin_ts")]).unwrap();
+        assert_eq!(names(&columns).last(), Some(&"TGL_IZIN_TS"));
+    }
+
+    #[test]
+    fn invalid_or_duplicate_names_are_rejected() {
+        assert!(column_map(&[("COLUMN_KDBANK", "KODE_BANK; DROP TABLE x")]).is_err());
+        assert!(column_map(&[("COLUMN_KDBANK", "norek")]).is_err());
+        assert!(column_map(&[("COLUMN_TGLIZIN_TS", "1st")]).is_err());
+    }
+
+    #[test]
+    fn audit_columns_are_split_between_insert_and_update() {
+        let columns = column_map(&[]).unwrap();
+        let column = |name: &str| columns.columns().iter().find(|c| c.name == name).unwrap();
+        assert!(column("CREATED_BY").on_insert && !column("CREATED_BY").on_update);
+        assert!(column("MODIFIED_BY").on_update && !column("MODIFIED_BY").on_insert);
+        assert!(!column("NOREK").detects_change());
+        assert!(column("NAMA_BANK").detects_change());
+    }
+}
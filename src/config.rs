@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::str::FromStr;
+use tokio::time::Duration;
+
+/// Parses an optional environment variable. Unset is `None`; set but
+/// unparsable is an error naming the variable.
+pub fn env_parse<T>(key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse::<T>()
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid value {:?} for {}: {}", value, key, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn env_secs(key: &str) -> Result<Option<Duration>> {
+    Ok(env_parse::<u64>(key)?.map(Duration::from_secs))
+}
+
+pub fn env_millis(key: &str) -> Result<Option<Duration>> {
+    Ok(env_parse::<u64>(key)?.map(Duration::from_millis))
+}
@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use std::env;
+use std::fmt;
+
+/// Used when TGLIZIN_FORMATS is not set.
+const DEFAULT_FORMATS: &str = "rfc3339,%Y-%m-%d,%d-%m-%Y";
+
+/// One accepted shape of `tglizin`.
+#[derive(Debug, Clone)]
+pub enum DateFormat {
+    /// `2023-01-05T17:30:00Z`, with or without fractional seconds, `Z` or an
+    /// offset. Converted to the configured zone.
+    Rfc3339,
+    /// A strftime pattern with a time part; read as local time.
+    DateTime(String),
+    /// A strftime pattern without a time part.
+    Date(String),
+}
+
+impl DateFormat {
+    fn parse(&self, value: &str, timezone: Tz) -> Option<NaiveDateTime> {
+        match self {
+            DateFormat::Rfc3339 => DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&timezone).naive_local()),
+            DateFormat::DateTime(pattern) => NaiveDateTime::parse_from_str(value, pattern).ok(),
+            DateFormat::Date(pattern) => NaiveDate::parse_from_str(value, pattern)
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN)),
+        }
+    }
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateFormat::Rfc3339 => f.write_str("rfc3339"),
+            DateFormat::DateTime(pattern) | DateFormat::Date(pattern) => f.write_str(pattern),
+        }
+    }
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("rfc3339") {
+            return Ok(DateFormat::Rfc3339);
+        }
+        if !value.contains('%') {
+            return Err(anyhow!("expected rfc3339 or a strftime pattern, got {:?}", value));
+        }
+        if ["%H", "%I", "%T", "%R"].iter().any(|time| value.contains(time)) {
+            Ok(DateFormat::DateTime(value.to_string()))
+        } else {
+            Ok(DateFormat::Date(value.to_string()))
+        }
+    }
+}
+
+/// What to do with a date field the gateway leaves null or empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NullPolicy {
+    Reject,
+    /// Store NULL in the column.
+    Null,
+    Default(NaiveDate),
+}
+
+impl std::str::FromStr for NullPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        match value.to_lowercase().as_str() {
+            "reject" => Ok(NullPolicy::Reject),
+            "null" => Ok(NullPolicy::Null),
+            lower if lower.starts_with("default:") => {
+                let date = &value["default:".len()..];
+                NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                    .map(NullPolicy::Default)
+                    .map_err(|e| anyhow!("invalid default date {:?}: {}", date, e))
+            }
+            other => Err(anyhow!("expected reject, null or default:YYYY-MM-DD, got {}", other)),
+        }
+    }
+}
+
+/// A parsed date and how it was obtained, for logs and run summaries.
+#[derive(Debug, Clone)]
+pub struct ParsedDate {
+    pub timestamp: NaiveDateTime,
+    /// The matching format, or `default` when the null policy filled it in.
+    pub format: String,
+}
+
+/// How gateway timestamps become local permit dates.
+#[derive(Debug, Clone)]
+pub struct DateConfig {
+    pub timezone: Tz,
+    /// Tried in order; the first match wins.
+    pub formats: Vec<DateFormat>,
+    pub null_policy: NullPolicy,
+}
+
+impl DateConfig {
+    /// Reads TGLIZIN_TIMEZONE (IANA name, default Asia/Jakarta),
+    /// TGLIZIN_FORMATS (comma separated `rfc3339` or strftime patterns) and
+    /// TGLIZIN_NULL_POLICY (`reject`, `null` or `default:YYYY-MM-DD`,
+    /// default reject).
+    pub fn from_env() -> Result<Self> {
+        let timezone = match env::var("TGLIZIN_TIMEZONE") {
+            Ok(name) => name
+                .trim()
+                .parse::<Tz>()
+                .map_err(|e| anyhow!("Invalid TGLIZIN_TIMEZONE {:?}: {}", name, e))?,
+            Err(_) => chrono_tz::Asia::Jakarta,
+        };
+        let formats = env::var("TGLIZIN_FORMATS")
+            .unwrap_or_else(|_| DEFAULT_FORMATS.to_string())
+            .split(',')
+            .filter(|format| !format.trim().is_empty())
+            .map(|format| {
+                format
+                    .parse::<DateFormat>()
+                    .map_err(|e| anyhow!("Invalid TGLIZIN_FORMATS entry: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if formats.is_empty() {
+            return Err(anyhow!("TGLIZIN_FORMATS must list at least one format"));
+        }
+        let null_policy = env::var("TGLIZIN_NULL_POLICY")
+            .map(|policy| policy.parse::<NullPolicy>())
+            .unwrap_or(Ok(NullPolicy::Reject))
+            .map_err(|e| anyhow!("Invalid TGLIZIN_NULL_POLICY: {}", e))?;
+
+        Ok(Self { timezone, formats, null_policy })
+    }
+
+    /// `Ok(None)` stores NULL; `Err` carries the reject reason.
+    pub fn parse_tglizin(&self, value: Option<&str>) -> Result<Option<ParsedDate>, String> {
+        let value = value.map(str::trim).filter(|value| !value.is_empty());
+
+        let Some(value) = value else {
+            return match &self.null_policy {
+                NullPolicy::Reject => Err("tglizin is missing and TGLIZIN_NULL_POLICY is reject".to_string()),
+                NullPolicy::Null => Ok(None),
+                NullPolicy::Default(date) => Ok(Some(ParsedDate {
+                    timestamp: date.and_time(NaiveTime::MIN),
+                    format: "default".to_string(),
+                })),
+            };
+        };
+
+        self.formats
+            .iter()
+            .find_map(|format| {
+                format.parse(value, self.timezone).map(|timestamp| ParsedDate {
+                    timestamp,
+                    format: format.to_string(),
+                })
+            })
+            .map(Some)
+            .ok_or_else(|| {
+                let tried = self.formats.iter().map(ToString::to_string).collect::<Vec<_>>();
+                format!("tglizin {:?} matches none of {}", value, tried.join(", "))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(null_policy: NullPolicy) -> DateConfig {
+        DateConfig {
+            timezone: chrono_tz::Asia::Jakarta,
+            formats: DEFAULT_FORMATS.split(',').map(|format| format.parse().unwrap()).collect(),
+            null_policy,
+        }
+    }
+
+    fn timestamp(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn rfc3339_is_converted_to_the_configured_zone() {
+        let parsed = config(NullPolicy::Reject).parse_tglizin(Some("2023-01-05T17:30:00Z")).unwrap().unwrap();
+        assert_eq!(parsed.timestamp, timestamp("2023-01-06 00:30:00"));
+        assert_eq!(parsed.format, "rfc3339");
+
+        let parsed = config(NullPolicy::Reject)
+            .parse_tglizin(Some("2023-01-05T10:00:00.250+07:00"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(), "2023-01-05 10:00:00");
+    }
+
+    #[test]
+    fn other_zones_shift_the_date_too() {
+        let config = DateConfig { timezone: chrono_tz::Asia::Jayapura, ..config(NullPolicy::Reject) };
+        let parsed = config.parse_tglizin(Some("2023-01-05T14:59:59Z")).unwrap().unwrap();
+        assert_eq!(parsed.timestamp, timestamp("2023-01-05 23:59:59"));
+    }
+
+    #[test]
+    fn dates_are_tried_in_order() {
+        let config = config(NullPolicy::Reject);
+        let parsed = config.parse_tglizin(Some(" 2023-01-05 ")).unwrap().unwrap();
+        assert_eq!(parsed.timestamp, timestamp("2023-01-05 00:00:00"));
+        assert_eq!(parsed.format, "%Y-%m-%d");
+
+        let parsed = config.parse_tglizin(Some("05-01-2023")).unwrap().unwrap();
+        assert_eq!(parsed.timestamp, timestamp("2023-01-05 00:00:00"));
+        assert_eq!(parsed.format, "%d-%m-%Y");
+    }
+
+    #[test]
+    fn unmatched_value_lists_the_formats_tried() {
+        let error = config(NullPolicy::Null).parse_tglizin(Some("5 Jan 2023")).unwrap_err();
+        assert!(error.contains("rfc3339, %Y-%m-%d, %d-%m-%Y"), "{}", error);
+    }
+
+    #[test]
+    fn missing_value_follows_the_null_policy() {
+        assert!(config(NullPolicy::Reject).parse_tglizin(None).is_err());
+        assert!(config(NullPolicy::Null).parse_tglizin(Some("  ")).unwrap().is_none());
+
+        let default = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let parsed = config(NullPolicy::Default(default)).parse_tglizin(None).unwrap().unwrap();
+        assert_eq!(parsed.timestamp, timestamp("2000-01-01 00:00:00"));
+        assert_eq!(parsed.format, "default");
+    }
+
+    #[test]
+    fn parses_format_and_policy_specs() {
+        assert!(matches!("RFC3339".parse::<DateFormat>().unwrap(), DateFormat::Rfc3339));
+        assert!(matches!("%d/%m/%Y %H:%M".parse::<DateFormat>().unwrap(), DateFormat::DateTime(_)));
+        assert!(matches!("%d/%m/%Y".parse::<DateFormat>().unwrap(), DateFormat::Date(_)));
+        assert!("dd/mm/yyyy".parse::<DateFormat>().is_err());
+
+        assert_eq!(
+            "Default: 2000-01-01".parse::<NullPolicy>().unwrap(),
+            NullPolicy::Default(NaiveDate::from_ymd_opt(2000, 1, 1).unwrap())
+        );
+        assert!("default:01-01-2000".parse::<NullPolicy>().is_err());
+        assert!("skip".parse::<NullPolicy>().is_err());
+    }
+}
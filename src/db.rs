@@ -1,131 +1,148 @@
+use crate::columns::{ColumnMap, SqlType, SqlValue};
+use crate::defaults::SatkerAttributes;
+use crate::ids::{KdSatker, NoRekening};
+use crate::logging;
 use crate::models::Rekening;
-use anyhow::Result;
-use log::{info, error, warn};
+use crate::quarantine::QuarantinedRecord;
+use crate::sink::{RekeningSink, RowResult, UpsertOutcome};
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::{HashMap, HashSet};
+use log::{info, error};
+use oracle::sql_type::ToSql;
 use r2d2_oracle::OracleConnectionManager;
 use r2d2::Pool;
 
 pub struct DatabaseHandler {
     pool: Pool<OracleConnectionManager>,
+    columns: ColumnMap,
+    merge_sql: String,
 }
 
-impl DatabaseHandler {
-    pub fn new(connection_string: &str) -> Result<Self> {
-        // Parse connection string (format: username/password@tns_name)
-        let parts: Vec<&str> = connection_string.split(['/', '@']).collect();
-        let username = parts[0];
-        let password = parts[1];
-        let tns_name = parts[2];
-        
-        let manager = OracleConnectionManager::new(username, password, tns_name);
-        let pool = Pool::new(manager)?;
-
-        Ok(Self { pool })
-    }
+/// Upsert keyed on NOREK: new rows get the CREATED_* audit columns, matched
+/// rows get MODIFIED_* and a VERSION bump. Matched rows whose values are all
+/// the same are skipped, so they affect no rows.
+fn merge_sql(columns: &ColumnMap) -> String {
+    let columns = columns.columns();
+    let select = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!(":{} as {}", i + 1, c.name))
+        .collect::<Vec<_>>()
+        .join(",\n                    ");
+    let update = columns
+        .iter()
+        .filter(|c| c.on_update)
+        .map(|c| format!("{0} = source.{0}", c.name))
+        .collect::<Vec<_>>()
+        .join(",\n                    ");
+    // DECODE treats two NULLs as equal, unlike `<>`
+    let changed = columns
+        .iter()
+        .filter(|c| c.detects_change())
+        .map(|c| format!("DECODE(target.{0}, source.{0}, 0, 1) = 1", c.name))
+        .collect::<Vec<_>>()
+        .join("\n                    OR ");
+    let inserted = columns.iter().filter(|c| c.on_insert);
+    let insert = inserted.clone().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ");
+    let values = inserted
+        .map(|c| format!("source.{}", c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
 
-    pub fn insert_rekening(&self, rekening: &Rekening) -> Result<()> {
-        info!("Starting insert for rekening: {:?}", rekening);
-        let conn = self.pool.get()?;
-        
-        match conn.execute(
-            "MERGE INTO V_BEN_REKONREK_SPRINT target
+    format!(
+        "MERGE INTO V_BEN_REKONREK_SPRINT target
             USING (
                 SELECT 
-                    :1 as KODE,
-                    :2 as KODE_SATKER,
-                    :3 as NAMA_BANK,
-                    :4 as NAMA_REK,
-                    :5 as NO_IZIN,
-                    :6 as NOREK,
-                    TO_DATE(:7, 'YYYY-MM-DD') as TGL_IZIN,
-                    :8 as OWNER,
-                    :9 as KODE_UNIT_TEKNIS,
-                    :10 as DESC_STATUS_REKENING,
-                    :11 as STATUS_REKENING,
-                    :12 as MATA_UANG
+                    {select}
                 FROM dual
             ) source
             ON (target.NOREK = source.NOREK)
             WHEN MATCHED THEN
                 UPDATE SET
-                    NAMA_BANK = source.NAMA_BANK,
-                    NAMA_REK = source.NAMA_REK,
-                    NO_IZIN = source.NO_IZIN,
-                    TGL_IZIN = source.TGL_IZIN,
-                    OWNER = source.OWNER,
-                    KODE_UNIT_TEKNIS = source.KODE_UNIT_TEKNIS,
-                    DESC_STATUS_REKENING = source.DESC_STATUS_REKENING,
-                    STATUS_REKENING = source.STATUS_REKENING,
-                    MATA_UANG = source.MATA_UANG,
-                    MODIFIED_BY = 'SYSTEM',
+                    {update},
                     MODIFIED_DATE = CURRENT_TIMESTAMP,
                     VERSION = VERSION + 1
+                WHERE {changed}
             WHEN NOT MATCHED THEN
                 INSERT (
-                    KODE, KODE_SATKER, NAMA_BANK, NAMA_REK,
-                    NO_IZIN, NOREK, TGL_IZIN, OWNER,
-                    KODE_UNIT_TEKNIS, DESC_STATUS_REKENING,
-                    STATUS_REKENING, MATA_UANG,
-                    CREATED_BY, CREATED_DATE, VERSION, DELETED
+                    {insert},
+                    CREATED_DATE, VERSION, DELETED
                 ) VALUES (
-                    source.KODE, source.KODE_SATKER, source.NAMA_BANK,
-                    source.NAMA_REK, source.NO_IZIN, source.NOREK,
-                    source.TGL_IZIN, source.OWNER, source.KODE_UNIT_TEKNIS,
-                    source.DESC_STATUS_REKENING, source.STATUS_REKENING,
-                    source.MATA_UANG, 'SYSTEM', CURRENT_TIMESTAMP, 1, 0
-                )",
-            &[
-                &rekening.kdjenis,           // KODE
-                &rekening.kd_satker,         // KODE_SATKER
-                &rekening.nama_bank,         // NAMA_BANK
-                &rekening.nama_rekening,     // NAMA_REK
-                &rekening.no_izin,          // NO_IZIN
-                &rekening.no_rekening,      // NOREK
-                &rekening.tgl_izin,         // TGL_IZIN
-                &"1",                       // OWNER
-                &"",                        // KODE_UNIT_TEKNIS
-                &rekening.desc_status_rekening, // DESC_STATUS_REKENING
-                &0,                         // STATUS_REKENING (default to 0)
-                &"IDR",                     // MATA_UANG
-            ],
-        ) {
-            Ok(rows) => {
-                info!("Affected rows for {}: {:?}", rekening.no_rekening, rows);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Error during upsert for {}: {:?}", rekening.no_rekening, e);
-                Err(e.into())
-            }
-        }
+                    {values},
+                    CURRENT_TIMESTAMP, 1, 0
+                )"
+    )
+}
+
+fn oracle_params<'a>(values: &'a [SqlValue<'a>]) -> Vec<&'a dyn ToSql> {
+    values
+        .iter()
+        .map(|value| match value {
+            SqlValue::Text(text) => text as &dyn ToSql,
+            SqlValue::Integer(number) => number as &dyn ToSql,
+            SqlValue::Date(date) => date as &dyn ToSql,
+            SqlValue::Timestamp(timestamp) => timestamp as &dyn ToSql,
+            SqlValue::Null(SqlType::Text) => &None::<String>,
+            SqlValue::Null(SqlType::Integer) => &None::<i64>,
+            SqlValue::Null(SqlType::Date) => &None::<NaiveDate>,
+            SqlValue::Null(SqlType::Timestamp) => &None::<NaiveDateTime>,
+        })
+        .collect()
+}
+
+impl DatabaseHandler {
+    pub fn new(connection_string: &str, columns: ColumnMap) -> Result<Self> {
+        // Parse connection string (format: username/password@tns_name)
+        let parts: Vec<&str> = connection_string.split(['/', '@']).collect();
+        let username = parts[0];
+        let password = parts[1];
+        let tns_name = parts[2];
+        
+        let manager = OracleConnectionManager::new(username, password, tns_name);
+        let pool = Pool::new(manager)?;
+        let merge_sql = merge_sql(&columns);
+
+        Ok(Self { pool, columns, merge_sql })
     }
 
-    pub fn get_active_satkers(&self, limit: i64) -> Result<Vec<String>> {
+    /// Satkers whose code is not six digits as stored, padding included,
+    /// are left out in the query, as the lookups by code would not find
+    /// them. Anything the query lets through that still does not parse is
+    /// logged and skipped.
+    pub fn get_active_satkers(&self, after: Option<&KdSatker>, limit: i64) -> Result<Vec<KdSatker>> {
         let conn = self.pool.get()?;
         let rows = conn.query(
             "SELECT kd_satker FROM V_BEN_REKON_REK_SATKER 
              WHERE is_active = 1 
-             ORDER BY last_fetch_date ASC NULLS FIRST 
-             FETCH FIRST :1 ROWS ONLY",
-            &[&limit],
+               AND REGEXP_LIKE(kd_satker, '^[0-9]{6}$')
+               AND (:1 IS NULL OR kd_satker > :2)
+             ORDER BY kd_satker
+             FETCH FIRST :3 ROWS ONLY",
+            // Bound by position, so the cursor goes in twice
+            &[&after.cloned(), &after.cloned(), &limit],
         )?;
 
         let mut satkers = Vec::new();
         for row_result in rows {
             let row = row_result?;
             let kd_satker: String = row.get(0)?;
-            satkers.push(kd_satker);
+            match kd_satker.parse::<KdSatker>() {
+                Ok(kd_satker) => satkers.push(kd_satker),
+                Err(e) => error!(event = "invalid_satker"; "Skipping satker {:?}: {}", kd_satker, e),
+            }
         }
 
         Ok(satkers)
     }
 
-    pub fn update_last_fetch_date(&self, kd_satker: &str) -> Result<()> {
+    pub fn update_last_fetch_date(&self, kd_satker: &KdSatker) -> Result<()> {
         let conn = self.pool.get()?;
         conn.execute(
             "UPDATE V_BEN_REKON_REK_SATKER 
              SET last_fetch_date = CURRENT_TIMESTAMP 
              WHERE kd_satker = :1",
-            &[&kd_satker],
+            &[kd_satker],
         )?;
 
         Ok(())
@@ -141,11 +158,11 @@ impl DatabaseHandler {
         Ok(count)
     }
 
-    pub fn verify_rekening(&self, norek: &str) -> Result<bool> {
+    pub fn verify_rekening(&self, norek: &NoRekening) -> Result<bool> {
         let conn = self.pool.get()?;
         let row = conn.query_row(
             "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT WHERE NOREK = :1",
-            &[&norek]
+            &[norek]
         )?;
         let count: i64 = row.get(0)?;
         Ok(count > 0)
@@ -170,100 +187,247 @@ impl DatabaseHandler {
         Ok(())
     }
 
-    pub fn insert_rekening_batch(&self, conn: &r2d2::PooledConnection<OracleConnectionManager>, rekening: &Rekening) -> Result<()> {
-        info!("Inserting rekening in batch: {}", rekening.no_rekening);
-        
-        // Add row count check before insert
-        let count_before = conn.query_row(
-            "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT",
-            &[]
+    /// One array DML execution of the MERGE for all rows. With batch
+    /// errors on, Oracle carries on past a failing row and reports it by
+    /// offset afterwards. The per-row counts tell unchanged rows apart, and
+    /// the NOREKs found beforehand tell updates from inserts.
+    pub fn insert_rekening_batch(
+        &self,
+        conn: &r2d2::PooledConnection<OracleConnectionManager>,
+        rekenings: &[Rekening],
+    ) -> Result<Vec<RowResult>> {
+        if rekenings.is_empty() {
+            return Ok(Vec::new());
+        }
+        info!("Upserting batch of {} rekening", rekenings.len());
+
+        let mut existing = Self::find_existing(conn, rekenings)?;
+        let rows = rekenings.iter().map(|rekening| self.columns.values(rekening)).collect::<Vec<_>>();
+        let mut batch = conn
+            .batch(&self.merge_sql, rows.len())
+            .with_batch_errors()
+            .with_row_counts()
+            .build()?;
+        for row in &rows {
+            batch.append_row(&oracle_params(row))?;
+        }
+
+        let mut failed = match batch.execute() {
+            Ok(()) => HashMap::new(),
+            Err(oracle::Error::BatchErrors(errors)) => errors
+                .iter()
+                .map(|e| {
+                    let index = e.offset() as usize;
+                    let norek = rekenings.get(index).map(|r| logging::norek(r.no_rekening.as_str()).to_string());
+                    error!(event = "upsert_failed"; "Error during batch upsert for {}: {}",
+                           norek.unwrap_or_default(), e.message());
+                    (index, e.message().to_string())
+                })
+                .collect(),
+            Err(e) => {
+                error!(event = "upsert_failed"; "Batch upsert of {} rows failed: {:?}", rekenings.len(), e);
+                return Err(e.into());
+            }
+        };
+        let row_counts = batch.row_counts()?;
+
+        Ok(rekenings
+            .iter()
+            .enumerate()
+            .map(|(index, rekening)| {
+                if let Some(message) = failed.remove(&index) {
+                    return Err(message);
+                }
+                // A NOREK repeated within the batch exists by its second row
+                let norek = rekening.no_rekening.as_str();
+                let outcome = match (row_counts.get(index).copied().unwrap_or(0), existing.contains(norek)) {
+                    (0, _) => UpsertOutcome::Unchanged,
+                    (_, true) => UpsertOutcome::Updated,
+                    (_, false) => UpsertOutcome::Inserted,
+                };
+                existing.insert(norek.to_string());
+                Ok(outcome)
+            })
+            .collect())
+    }
+
+    /// NOREKs of the batch already in V_BEN_REKONREK_SPRINT. Only used to
+    /// label the MERGE's row counts, so nothing is locked: the table is
+    /// behind a view, and a row another session adds or removes in between
+    /// at worst labels one upsert as inserted instead of updated or the
+    /// other way round.
+    fn find_existing(
+        conn: &r2d2::PooledConnection<OracleConnectionManager>,
+        rekenings: &[Rekening],
+    ) -> Result<HashSet<String>> {
+        let mut existing = HashSet::new();
+        // Oracle allows at most 1000 expressions in an IN list
+        for chunk in rekenings.chunks(1000) {
+            let placeholders = (1..=chunk.len()).map(|i| format!(":{}", i)).collect::<Vec<_>>().join(", ");
+            let sql = format!(
+                "SELECT NOREK FROM V_BEN_REKONREK_SPRINT WHERE NOREK IN ({})",
+                placeholders
+            );
+            let params = chunk.iter().map(|r| &r.no_rekening as &dyn ToSql).collect::<Vec<_>>();
+            for row in conn.query(&sql, &params)? {
+                existing.insert(row?.get::<_, String>(0)?);
+            }
+        }
+        Ok(existing)
+    }
+}
+
+impl RekeningSink for DatabaseHandler {
+    type Transaction = r2d2::PooledConnection<OracleConnectionManager>;
+
+    fn get_active_satkers(&self, after: Option<&KdSatker>, limit: i64) -> Result<Vec<KdSatker>> {
+        DatabaseHandler::get_active_satkers(self, after, limit)
+    }
+
+    fn update_last_fetch_date(&self, kd_satker: &KdSatker) -> Result<()> {
+        DatabaseHandler::update_last_fetch_date(self, kd_satker)
+    }
+
+    fn satker_attributes(&self, kd_satker: &KdSatker) -> Result<SatkerAttributes> {
+        let conn = self.pool.get()?;
+        let row = conn.query_row(
+            "SELECT owner, kode_unit_teknis, mata_uang FROM V_BEN_REKON_REK_SATKER
+             WHERE kd_satker = :1",
+            &[kd_satker],
         )?;
-        let before_count: i64 = count_before.get(0)?;
-        
-        match conn.execute(
-            "MERGE INTO V_BEN_REKONREK_SPRINT target
+
+        Ok(SatkerAttributes {
+            owner: row.get(0)?,
+            kode_unit_teknis: row.get(1)?,
+            mata_uang: row.get(2)?,
+        })
+    }
+
+    fn begin_transaction(&self) -> Result<Self::Transaction> {
+        DatabaseHandler::begin_transaction(self)
+    }
+
+    fn upsert_rekening(&self, tx: &Self::Transaction, rekening: &Rekening) -> Result<UpsertOutcome> {
+        match self.insert_rekening_batch(tx, std::slice::from_ref(rekening))?.pop() {
+            Some(result) => result.map_err(|message| anyhow!(message)),
+            None => Err(anyhow!("Upsert returned no result")),
+        }
+    }
+
+    fn upsert_rekening_batch(&self, tx: &Self::Transaction, rekenings: &[Rekening]) -> Result<Vec<RowResult>> {
+        self.insert_rekening_batch(tx, rekenings)
+    }
+
+    fn queue_for_review(&self, tx: &Self::Transaction, rekening: &Rekening, reason: &str) -> Result<()> {
+        info!("Queueing rekening for review: {}", logging::norek(rekening.no_rekening.as_str()));
+        tx.execute(
+            "MERGE INTO V_BEN_REKONREK_REVIEW target
             USING (
-                SELECT 
-                    :1 as KODE,
-                    :2 as KODE_SATKER,
-                    :3 as NAMA_BANK,
-                    :4 as NAMA_REK,
-                    :5 as NO_IZIN,
-                    :6 as NOREK,
-                    TO_DATE(:7, 'YYYY-MM-DD') as TGL_IZIN,
-                    :8 as OWNER,
-                    :9 as KODE_UNIT_TEKNIS,
-                    :10 as DESC_STATUS_REKENING,
-                    :11 as STATUS_REKENING,
-                    :12 as MATA_UANG
+                SELECT :1 as KODE_SATKER, :2 as NOREK, :3 as KDSTATUS,
+                       :4 as NMSTATUS, :5 as REASON
                 FROM dual
             ) source
             ON (target.NOREK = source.NOREK)
             WHEN MATCHED THEN
                 UPDATE SET
-                    NAMA_BANK = source.NAMA_BANK,
-                    NAMA_REK = source.NAMA_REK,
-                    NO_IZIN = source.NO_IZIN,
-                    TGL_IZIN = source.TGL_IZIN,
-                    OWNER = source.OWNER,
-                    KODE_UNIT_TEKNIS = source.KODE_UNIT_TEKNIS,
-                    DESC_STATUS_REKENING = source.DESC_STATUS_REKENING,
-                    STATUS_REKENING = source.STATUS_REKENING,
-                    MATA_UANG = source.MATA_UANG,
-                    MODIFIED_BY = 'SYSTEM',
-                    MODIFIED_DATE = CURRENT_TIMESTAMP,
-                    VERSION = VERSION + 1
+                    KODE_SATKER = source.KODE_SATKER,
+                    KDSTATUS = source.KDSTATUS,
+                    NMSTATUS = source.NMSTATUS,
+                    REASON = source.REASON,
+                    MODIFIED_DATE = CURRENT_TIMESTAMP
             WHEN NOT MATCHED THEN
-                INSERT (
-                    KODE, KODE_SATKER, NAMA_BANK, NAMA_REK,
-                    NO_IZIN, NOREK, TGL_IZIN, OWNER,
-                    KODE_UNIT_TEKNIS, DESC_STATUS_REKENING,
-                    STATUS_REKENING, MATA_UANG,
-                    CREATED_BY, CREATED_DATE, VERSION, DELETED
-                ) VALUES (
-                    source.KODE, source.KODE_SATKER, source.NAMA_BANK,
-                    source.NAMA_REK, source.NO_IZIN, source.NOREK,
-                    source.TGL_IZIN, source.OWNER, source.KODE_UNIT_TEKNIS,
-                    source.DESC_STATUS_REKENING, source.STATUS_REKENING,
-                    source.MATA_UANG, 'SYSTEM', CURRENT_TIMESTAMP, 1, 0
-                )",
+                INSERT (KODE_SATKER, NOREK, KDSTATUS, NMSTATUS, REASON, CREATED_DATE)
+                VALUES (source.KODE_SATKER, source.NOREK, source.KDSTATUS,
+                        source.NMSTATUS, source.REASON, CURRENT_TIMESTAMP)",
             &[
-                &rekening.kdjenis,           // KODE
-                &rekening.kd_satker,         // KODE_SATKER
-                &rekening.nama_bank,         // NAMA_BANK
-                &rekening.nama_rekening,     // NAMA_REK
-                &rekening.no_izin,          // NO_IZIN
-                &rekening.no_rekening,      // NOREK
-                &rekening.tgl_izin,         // TGL_IZIN
-                &"1",                       // OWNER
-                &"",                        // KODE_UNIT_TEKNIS
-                &rekening.desc_status_rekening, // DESC_STATUS_REKENING
-                &0,                         // STATUS_REKENING (default to 0)
-                &"IDR",                     // MATA_UANG
+                &rekening.kd_satker,
+                &rekening.no_rekening,
+                &rekening.kode_status,
+                &rekening.desc_status_rekening,
+                &reason,
             ],
-        ) {
-            Ok(rows) => {
-                let count_after = conn.query_row(
-                    "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT",
-                    &[]
-                )?;
-                let after_count: i64 = count_after.get(0)?;
-                
-                info!("Row counts - Before: {}, After: {}, Difference: {}", 
-                      before_count, after_count, after_count - before_count);
-                
-                if after_count <= before_count {
-                    warn!("No new rows added for rekening: {} (Before: {}, After: {})", 
-                          rekening.no_rekening, before_count, after_count);
-                }
-                
-                Ok(())
-            }
-            Err(e) => {
-                error!("Error during batch insert for {}: {:?}", rekening.no_rekening, e);
-                error!("Oracle error: {}", e);
-                Err(e.into())
-            }
+        )?;
+        Ok(())
+    }
+
+    fn quarantine(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()> {
+        tx.execute(
+            "MERGE INTO V_BEN_REKONREK_QUARANTINE target
+            USING (
+                SELECT :1 as KODE_SATKER, :2 as RECORD_HASH, :3 as RAW,
+                       :4 as REASON, :5 as RUN_ID, :6 as QUARANTINED_AT
+                FROM dual
+            ) source
+            ON (target.KODE_SATKER = source.KODE_SATKER AND target.RECORD_HASH = source.RECORD_HASH)
+            WHEN MATCHED THEN
+                UPDATE SET
+                    REASON = source.REASON,
+                    RUN_ID = source.RUN_ID,
+                    QUARANTINED_AT = source.QUARANTINED_AT
+            WHEN NOT MATCHED THEN
+                INSERT (KODE_SATKER, RECORD_HASH, RAW, REASON, RUN_ID, QUARANTINED_AT)
+                VALUES (source.KODE_SATKER, source.RECORD_HASH, source.RAW,
+                        source.REASON, source.RUN_ID, source.QUARANTINED_AT)",
+            &[
+                &record.kd_satker,
+                &record.record_hash,
+                &record.raw,
+                &record.reason,
+                &record.run_id,
+                &record.quarantined_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_quarantined(&self, kd_satker: Option<&KdSatker>) -> Result<Vec<QuarantinedRecord>> {
+        let conn = self.pool.get()?;
+        let rows = conn.query(
+            "SELECT KODE_SATKER, RECORD_HASH, RAW, REASON, RUN_ID, QUARANTINED_AT
+             FROM V_BEN_REKONREK_QUARANTINE
+             WHERE :1 IS NULL OR KODE_SATKER = :2
+             ORDER BY QUARANTINED_AT",
+            // Oracle binds every placeholder in plain SQL by position, even
+            // a repeated one, so the satker goes in twice
+            &[&kd_satker.cloned(), &kd_satker.cloned()],
+        )?;
+
+        let mut records = Vec::new();
+        for row_result in rows {
+            let row = row_result?;
+            records.push(QuarantinedRecord {
+                kd_satker: row.get(0)?,
+                record_hash: row.get(1)?,
+                raw: row.get(2)?,
+                reason: row.get(3)?,
+                run_id: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                quarantined_at: row.get(5)?,
+            });
         }
+
+        Ok(records)
+    }
+
+    fn release_quarantined(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()> {
+        tx.execute(
+            "DELETE FROM V_BEN_REKONREK_QUARANTINE
+             WHERE KODE_SATKER = :1 AND RECORD_HASH = :2",
+            &[&record.kd_satker, &record.record_hash],
+        )?;
+        Ok(())
+    }
+
+    fn checkpoint(&self, tx: &Self::Transaction) -> Result<()> {
+        DatabaseHandler::commit_transaction(tx)?;
+        tx.execute("SET TRANSACTION READ WRITE", &[])?;
+        Ok(())
+    }
+
+    fn commit(&self, tx: &Self::Transaction) -> Result<()> {
+        DatabaseHandler::commit_transaction(tx)
     }
-} 
\ No newline at end of file
+
+    fn rollback(&self, tx: &Self::Transaction) -> Result<()> {
+        DatabaseHandler::rollback_transaction(tx)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::env_parse;
+use crate::logging::RUN_ID;
+use anyhow::Result;
+use std::env;
+
+/// Values for the columns the gateway does not supply, resolved per satker.
+#[derive(Debug, Clone)]
+pub struct RecordDefaults {
+    pub owner: String,
+    pub kode_unit_teknis: String,
+    pub mata_uang: String,
+    /// Written to CREATED_BY on insert and MODIFIED_BY on update.
+    pub audit_user: String,
+}
+
+/// Per-satker overrides read from V_BEN_REKON_REK_SATKER. NULL keeps the
+/// configured default.
+#[derive(Debug, Clone, Default)]
+pub struct SatkerAttributes {
+    pub owner: Option<String>,
+    pub kode_unit_teknis: Option<String>,
+    pub mata_uang: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnDefaults {
+    owner: String,
+    kode_unit_teknis: String,
+    mata_uang: String,
+    audit_user: String,
+    instance: String,
+    per_satker: bool,
+}
+
+impl ColumnDefaults {
+    /// Reads DEFAULT_OWNER ("1"), DEFAULT_KODE_UNIT_TEKNIS (""),
+    /// DEFAULT_MATA_UANG ("IDR"), AUDIT_USER ("SYSTEM"), JOB_INSTANCE
+    /// ("gwsprint") and SATKER_ATTRIBUTES (false). AUDIT_USER may contain
+    /// `{instance}` and `{run_id}`.
+    pub fn from_env() -> Result<Self> {
+        let var = |key: &str, default: &str| env::var(key).unwrap_or_else(|_| default.to_string());
+
+        Ok(Self {
+            owner: var("DEFAULT_OWNER", "1"),
+            kode_unit_teknis: var("DEFAULT_KODE_UNIT_TEKNIS", ""),
+            mata_uang: var("DEFAULT_MATA_UANG", "IDR"),
+            audit_user: var("AUDIT_USER", "SYSTEM"),
+            instance: var("JOB_INSTANCE", "gwsprint"),
+            per_satker: env_parse("SATKER_ATTRIBUTES")?.unwrap_or(false),
+        })
+    }
+
+    /// Whether satker rows carry OWNER, KODE_UNIT_TEKNIS and MATA_UANG
+    /// overrides worth looking up.
+    pub fn per_satker(&self) -> bool {
+        self.per_satker
+    }
+
+    /// Must run inside the run's `RUN_ID` scope for `{run_id}` to resolve.
+    pub fn for_satker(&self, attributes: SatkerAttributes) -> RecordDefaults {
+        let run_id = RUN_ID.try_with(|id| id.clone()).unwrap_or_default();
+
+        RecordDefaults {
+            owner: attributes.owner.unwrap_or_else(|| self.owner.clone()),
+            kode_unit_teknis: attributes
+                .kode_unit_teknis
+                .unwrap_or_else(|| self.kode_unit_teknis.clone()),
+            mata_uang: attributes.mata_uang.unwrap_or_else(|| self.mata_uang.clone()),
+            audit_user: self
+                .audit_user
+                .replace("{instance}", &self.instance)
+                .replace("{run_id}", &run_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured() -> ColumnDefaults {
+        ColumnDefaults {
+            owner: "1".to_string(),
+            kode_unit_teknis: "UT".to_string(),
+            mata_uang: "IDR".to_string(),
+            audit_user: "{instance}/{run_id}".to_string(),
+            instance: "gwsprint".to_string(),
+            per_satker: true,
+        }
+    }
+
+    #[test]
+    fn satker_attributes_override_the_configured_defaults() {
+        let attributes = SatkerAttributes {
+            owner: Some("2".to_string()),
+            kode_unit_teknis: None,
+            mata_uang: Some("USD".to_string()),
+        };
+        let defaults = configured().for_satker(attributes);
+        assert_eq!(defaults.owner, "2");
+        assert_eq!(defaults.kode_unit_teknis, "UT");
+        assert_eq!(defaults.mata_uang, "USD");
+    }
+
+    #[test]
+    fn missing_attributes_keep_the_configured_defaults() {
+        let defaults = configured().for_satker(SatkerAttributes::default());
+        assert_eq!(defaults.owner, "1");
+        assert_eq!(defaults.kode_unit_teknis, "UT");
+        assert_eq!(defaults.mata_uang, "IDR");
+    }
+
+    #[test]
+    fn audit_user_resolves_instance_and_run_id() {
+        let defaults = RUN_ID.sync_scope("run-1".to_string(), || configured().for_satker(SatkerAttributes::default()));
+        assert_eq!(defaults.audit_user, "gwsprint/run-1");
+
+        let outside_run = configured().for_satker(SatkerAttributes::default());
+        assert_eq!(outside_run.audit_user, "gwsprint/");
+    }
+}
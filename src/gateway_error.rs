@@ -0,0 +1,125 @@
+use crate::models::RekeningResponse;
+use reqwest::StatusCode;
+use std::fmt;
+
+/// Why the gateway did not hand over a satker's accounts. Built from the
+/// HTTP status, or from `code`/`message` when the body says `success: false`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayError {
+    Unauthorized { message: String },
+    SatkerNotFound { message: String },
+    QuotaExceeded { message: String },
+    UpstreamFailure { code: String, message: String },
+    MalformedPayload { detail: String },
+    Unknown { code: String, message: String },
+}
+
+impl GatewayError {
+    pub fn from_status(status: StatusCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => GatewayError::Unauthorized { message },
+            StatusCode::NOT_FOUND => GatewayError::SatkerNotFound { message },
+            StatusCode::TOO_MANY_REQUESTS => GatewayError::QuotaExceeded { message },
+            status if status.is_server_error() => GatewayError::UpstreamFailure {
+                code: status.as_u16().to_string(),
+                message,
+            },
+            status => GatewayError::Unknown {
+                code: status.as_u16().to_string(),
+                message,
+            },
+        }
+    }
+
+    /// Maps the body of an unsuccessful response. The gateway reuses HTTP
+    /// numbers in `code`, optionally spelled out as names.
+    pub fn from_response(response: &RekeningResponse) -> Self {
+        Self::from_code(&response.code, &response.message)
+    }
+
+    pub fn from_code(code: &str, message: &str) -> Self {
+        let message = message.to_string();
+        let code = code.trim();
+
+        if let Some(status) = code.parse::<u16>().ok().and_then(|c| StatusCode::from_u16(c).ok()) {
+            return Self::from_status(status, message);
+        }
+
+        match code.to_uppercase().as_str() {
+            "UNAUTHORIZED" | "FORBIDDEN" | "INVALID_TOKEN" => GatewayError::Unauthorized { message },
+            "NOT_FOUND" | "SATKER_NOT_FOUND" => GatewayError::SatkerNotFound { message },
+            "TOO_MANY_REQUESTS" | "QUOTA_EXCEEDED" | "RATE_LIMITED" => {
+                GatewayError::QuotaExceeded { message }
+            }
+            "INTERNAL_ERROR" | "UPSTREAM_ERROR" | "SERVICE_UNAVAILABLE" => {
+                GatewayError::UpstreamFailure { code: code.to_string(), message }
+            }
+            _ => GatewayError::Unknown { code: code.to_string(), message },
+        }
+    }
+
+    /// Short label for logs and run summaries.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GatewayError::Unauthorized { .. } => "unauthorized",
+            GatewayError::SatkerNotFound { .. } => "satker_not_found",
+            GatewayError::QuotaExceeded { .. } => "quota_exceeded",
+            GatewayError::UpstreamFailure { .. } => "upstream_failure",
+            GatewayError::MalformedPayload { .. } => "malformed_payload",
+            GatewayError::Unknown { .. } => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for GatewayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GatewayError::Unauthorized { message } => write!(f, "Gateway refused credentials: {}", message),
+            GatewayError::SatkerNotFound { message } => write!(f, "Satker not known to gateway: {}", message),
+            GatewayError::QuotaExceeded { message } => write!(f, "Gateway quota exceeded: {}", message),
+            GatewayError::UpstreamFailure { code, message } => {
+                write!(f, "Gateway upstream failure ({}): {}", code, message)
+            }
+            GatewayError::MalformedPayload { detail } => write!(f, "Malformed gateway payload: {}", detail),
+            GatewayError::Unknown { code, message } => write!(f, "Gateway error ({}): {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for GatewayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_codes_map_like_http_statuses() {
+        assert_eq!(GatewayError::from_code("401", "no").kind(), "unauthorized");
+        assert_eq!(GatewayError::from_code("403", "no").kind(), "unauthorized");
+        assert_eq!(GatewayError::from_code(" 404 ", "gone").kind(), "satker_not_found");
+        assert_eq!(GatewayError::from_code("429", "slow down").kind(), "quota_exceeded");
+        assert_eq!(
+            GatewayError::from_code("503", "down"),
+            GatewayError::UpstreamFailure { code: "503".to_string(), message: "down".to_string() }
+        );
+        assert_eq!(GatewayError::from_code("418", "teapot").kind(), "unknown");
+    }
+
+    #[test]
+    fn named_codes_are_case_insensitive() {
+        assert_eq!(GatewayError::from_code("invalid_token", "").kind(), "unauthorized");
+        assert_eq!(GatewayError::from_code("SATKER_NOT_FOUND", "").kind(), "satker_not_found");
+        assert_eq!(GatewayError::from_code("Rate_Limited", "").kind(), "quota_exceeded");
+        assert_eq!(GatewayError::from_code("upstream_error", "").kind(), "upstream_failure");
+    }
+
+    #[test]
+    fn unknown_codes_keep_code_and_message() {
+        assert_eq!(
+            GatewayError::from_code("E42", "odd"),
+            GatewayError::Unknown { code: "E42".to_string(), message: "odd".to_string() }
+        );
+        assert_eq!(GatewayError::from_code("", "blank").kind(), "unknown");
+    }
+}
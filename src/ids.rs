@@ -0,0 +1,215 @@
+use crate::logging;
+use oracle::sql_type::{FromSql, OracleType, ToSql, ToSqlNull};
+use oracle::{Connection, SqlValue as OracleValue};
+use rusqlite::types::{FromSql as SqliteFromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Why a string is not a valid identifier. Messages leave out the value,
+/// as account and permit numbers are PII.
+#[derive(Debug, Clone)]
+pub struct InvalidIdentifier(&'static str);
+
+impl fmt::Display for InvalidIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for InvalidIdentifier {}
+
+/// Satker code: exactly six digits.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KdSatker(String);
+
+impl FromStr for KdSatker {
+    type Err = InvalidIdentifier;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.len() != 6 || !value.chars().all(|c| c.is_ascii_digit()) {
+            return Err(InvalidIdentifier("kdsatker must be exactly 6 digits"));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl fmt::Debug for KdSatker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KdSatker({})", self.0)
+    }
+}
+
+/// Account number: digits, optionally grouped with `-`, `.` or whitespace.
+/// Kept as given; the norek_digits normalization step strips the separators,
+/// and records which value it changed.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NoRekening(String);
+
+impl FromStr for NoRekening {
+    type Err = InvalidIdentifier;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if !value.chars().all(|c| c.is_ascii_digit() || matches!(c, '-' | '.') || c.is_whitespace()) {
+            return Err(InvalidIdentifier(
+                "NOREK may only contain digits and the separators '-', '.' and ' '",
+            ));
+        }
+        if !value.chars().any(|c| c.is_ascii_digit()) {
+            return Err(InvalidIdentifier("NOREK has no digits"));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl fmt::Debug for NoRekening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NoRekening({})", logging::norek(&self.0))
+    }
+}
+
+/// Permit number. Free-form, but never blank or carrying control
+/// characters.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NoIzin(String);
+
+impl FromStr for NoIzin {
+    type Err = InvalidIdentifier;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(InvalidIdentifier("noizin is blank"));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(InvalidIdentifier("noizin contains control characters"));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl fmt::Debug for NoIzin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NoIzin({})", logging::pii(&self.0))
+    }
+}
+
+/// Conversions shared by the identifier types: string access, serde and
+/// both database backends, all going through `FromStr` when reading.
+macro_rules! identifier {
+    ($name:ident) => {
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = InvalidIdentifier;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                value.parse()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                value.0
+            }
+        }
+
+        impl ToSqlNull for $name {
+            fn oratype_for_null(conn: &Connection) -> oracle::Result<OracleType> {
+                String::oratype_for_null(conn)
+            }
+        }
+
+        impl ToSql for $name {
+            fn oratype(&self, conn: &Connection) -> oracle::Result<OracleType> {
+                self.0.oratype(conn)
+            }
+
+            fn to_sql(&self, val: &mut OracleValue) -> oracle::Result<()> {
+                self.0.to_sql(val)
+            }
+        }
+
+        impl FromSql for $name {
+            fn from_sql(val: &OracleValue) -> oracle::Result<Self> {
+                String::from_sql(val)?
+                    .parse()
+                    .map_err(|e| oracle::Error::ParseError(Box::new(e)))
+            }
+        }
+
+        impl rusqlite::ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.0.as_str()))
+            }
+        }
+
+        impl SqliteFromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                value.as_str()?.parse().map_err(|e| FromSqlError::Other(Box::new(e)))
+            }
+        }
+    };
+}
+
+identifier!(KdSatker);
+identifier!(NoRekening);
+identifier!(NoIzin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kd_satker_is_six_trimmed_digits() {
+        assert_eq!(" 123456 ".parse::<KdSatker>().unwrap().as_str(), "123456");
+        assert!("12345".parse::<KdSatker>().is_err());
+        assert!("1234567".parse::<KdSatker>().is_err());
+        assert!("12345A".parse::<KdSatker>().is_err());
+        assert!("１２３４５６".parse::<KdSatker>().is_err());
+    }
+
+    #[test]
+    fn no_rekening_is_validated_but_kept_as_given() {
+        assert_eq!("012345678".parse::<NoRekening>().unwrap().as_str(), "012345678");
+        assert_eq!("0123-45.67 8".parse::<NoRekening>().unwrap().as_str(), "0123-45.67 8");
+        assert!("12-AB".parse::<NoRekening>().is_err());
+        assert!(" - . ".parse::<NoRekening>().is_err());
+        assert!("".parse::<NoRekening>().is_err());
+    }
+
+    #[test]
+    fn no_izin_is_trimmed_and_printable() {
+        assert_eq!(" SI-123/2023 ".parse::<NoIzin>().unwrap().as_str(), "SI-123/2023");
+        assert!("   ".parse::<NoIzin>().is_err());
+        assert!("SI-1\u{7}".parse::<NoIzin>().is_err());
+    }
+
+    #[test]
+    fn errors_leave_out_the_value() {
+        let error = "secret-9".parse::<NoRekening>().unwrap_err().to_string();
+        assert!(!error.contains("secret"), "{}", error);
+    }
+
+    #[test]
+    fn deserializing_validates() {
+        let satker: KdSatker = serde_json::from_str(r#"" 654321""#).unwrap();
+        assert_eq!(satker.to_string(), "654321");
+        assert!(serde_json::from_str::<KdSatker>(r#""65432""#).is_err());
+        assert_eq!(serde_json::to_string(&satker).unwrap(), r#""654321""#);
+    }
+}
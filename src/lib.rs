@@ -0,0 +1,28 @@
+//! Loads SPRINT bank accounts from the gateway into V_BEN_REKONREK_SPRINT.
+//! The binary wires these modules up from the environment.
+
+pub mod api_client;
+pub mod auth;
+pub mod batch_processor;
+pub mod cassette;
+pub mod circuit_breaker;
+pub mod columns;
+pub mod config;
+pub mod dates;
+pub mod db;
+pub mod defaults;
+pub mod gateway_error;
+pub mod ids;
+pub mod logging;
+pub mod models;
+pub mod normalize;
+pub mod quarantine;
+pub mod rate_limit;
+pub mod retry;
+pub mod sink;
+pub mod source;
+pub mod sqlite_db;
+pub mod status_map;
+pub mod streaming;
+pub mod tls;
+pub mod validation;
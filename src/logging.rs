@@ -0,0 +1,175 @@
+use anyhow::Result;
+use chrono::Utc;
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use serde_json::{Map, Value as JsonValue};
+use std::fmt;
+use std::io::Write;
+use std::sync::OnceLock;
+
+use crate::config::env_parse;
+
+tokio::task_local! {
+    /// Id of the batch run the current future belongs to.
+    pub static RUN_ID: String;
+    /// Satker the current future is processing.
+    pub static SATKER: String;
+}
+
+/// `tokio::task::spawn_blocking` that carries the caller's RUN_ID and
+/// SATKER over, as task-locals do not reach the blocking thread.
+pub fn spawn_blocking<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let run_id = RUN_ID.try_with(String::clone).ok();
+    let satker = SATKER.try_with(String::clone).ok();
+    tokio::task::spawn_blocking(move || {
+        let f = move || match satker {
+            Some(satker) => SATKER.sync_scope(satker, f),
+            None => f(),
+        };
+        match run_id {
+            Some(run_id) => RUN_ID.sync_scope(run_id, f),
+            None => f(),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogConfig {
+    /// Mask NOREK, NMREK and NOIZIN wherever they are logged.
+    pub mask_pii: bool,
+    /// Dump raw gateway bodies at debug level.
+    pub log_raw_bodies: bool,
+    pub json: bool,
+}
+
+static CONFIG: OnceLock<LogConfig> = OnceLock::new();
+
+fn config() -> LogConfig {
+    *CONFIG.get_or_init(|| LogConfig {
+        mask_pii: true,
+        log_raw_bodies: false,
+        json: true,
+    })
+}
+
+/// Installs the logger. Reads LOG_MASK_PII (default true), LOG_RAW_BODIES
+/// (default false) and LOG_FORMAT (`json` or `text`, default json) on top
+/// of the usual RUST_LOG filter.
+pub fn init() -> Result<()> {
+    let json = match std::env::var("LOG_FORMAT") {
+        Ok(format) => format.eq_ignore_ascii_case("json"),
+        Err(_) => true,
+    };
+    let log_config = LogConfig {
+        mask_pii: env_parse("LOG_MASK_PII")?.unwrap_or(true),
+        log_raw_bodies: env_parse("LOG_RAW_BODIES")?.unwrap_or(false),
+        json,
+    };
+    let _ = CONFIG.set(log_config);
+
+    let mut builder = env_logger::Builder::from_default_env();
+    if log_config.json {
+        builder.format(|buf, record| {
+            let mut line = Map::new();
+            line.insert("ts".into(), Utc::now().to_rfc3339().into());
+            line.insert("level".into(), record.level().as_str().into());
+            line.insert("target".into(), record.target().into());
+            if let Ok(run_id) = RUN_ID.try_with(|id| id.clone()) {
+                line.insert("run_id".into(), run_id.into());
+            }
+            if let Ok(satker) = SATKER.try_with(|satker| satker.clone()) {
+                line.insert("satker".into(), satker.into());
+            }
+            let _ = record.key_values().visit(&mut JsonFields(&mut line));
+            line.insert("msg".into(), record.args().to_string().into());
+
+            writeln!(buf, "{}", JsonValue::Object(line))
+        });
+    }
+    builder.init();
+    Ok(())
+}
+
+/// Copies `log` key-values such as `event = "..."` into the JSON line.
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        self.0.insert(key.as_str().to_string(), value.to_string().into());
+        Ok(())
+    }
+}
+
+pub fn raw_bodies_enabled() -> bool {
+    config().log_raw_bodies
+}
+
+/// Log-safe view of a sensitive value. Displays the masked form unless
+/// masking was switched off with LOG_MASK_PII=false.
+pub struct Masked<'a> {
+    value: &'a str,
+    keep_last: usize,
+}
+
+impl fmt::Display for Masked<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !config().mask_pii {
+            return f.write_str(self.value);
+        }
+
+        let chars: Vec<char> = self.value.chars().collect();
+        let keep = self.keep_last.min(chars.len() / 2);
+        let hidden = chars.len() - keep;
+        let visible: String = chars[hidden..].iter().collect();
+        write!(f, "{}{}", "*".repeat(hidden), visible)
+    }
+}
+
+impl fmt::Debug for Masked<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+/// Account number: only the last 4 characters stay visible.
+pub fn norek(value: &str) -> Masked<'_> {
+    Masked { value, keep_last: 4 }
+}
+
+/// Account holder names and permit numbers are hidden entirely.
+pub fn pii(value: &str) -> Masked<'_> {
+    Masked { value, keep_last: 0 }
+}
+
+pub fn opt_norek(value: &Option<String>) -> Option<Masked<'_>> {
+    value.as_deref().map(norek)
+}
+
+pub fn opt_pii(value: &Option<String>) -> Option<Masked<'_>> {
+    value.as_deref().map(pii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn_blocking_keeps_run_and_satker() {
+        let seen = RUN_ID
+            .scope("run-1".to_string(), SATKER.scope("123456".to_string(), async {
+                spawn_blocking(|| (RUN_ID.with(String::clone), SATKER.with(String::clone))).await
+            }))
+            .await
+            .unwrap();
+        assert_eq!(seen, ("run-1".to_string(), "123456".to_string()));
+    }
+
+    #[tokio::test]
+    async fn spawn_blocking_without_scope() {
+        let seen = spawn_blocking(|| RUN_ID.try_with(String::clone).is_err()).await.unwrap();
+        assert!(seen);
+    }
+}
@@ -1,26 +1,66 @@
-mod api_client;
-mod db;
-mod models;
-mod batch_processor;
-
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use dotenv::dotenv;
 use log::{error, info};
 use std::env;
 use tokio_cron_scheduler::{Job, JobScheduler};
+use uuid::Uuid;
 
-use crate::api_client::ApiClient;
-use crate::db::DatabaseHandler;
-use crate::batch_processor::BatchProcessor;
+use gwsprint::api_client::{ApiClient, ApiClientConfig};
+use gwsprint::auth::TokenProvider;
+use gwsprint::db::DatabaseHandler;
+use gwsprint::defaults::ColumnDefaults;
+use gwsprint::batch_processor::{BatchProcessor, CircuitOpenAction};
+use gwsprint::columns::ColumnMap;
+use gwsprint::config::env_parse;
+use gwsprint::quarantine::Quarantine;
+use gwsprint::sink::RekeningSink;
+use gwsprint::source::{FixtureSource, RekeningSource};
+use gwsprint::sqlite_db::SqliteHandler;
+use gwsprint::validation::RecordRules;
+use gwsprint::{logging, quarantine};
 
 async fn process_data() -> Result<()> {
-    let gateway_url = env::var("GATEWAY_URL")?;
-    let token = env::var("API_TOKEN")?;
-    let connection_string = env::var("ORACLE_CONNECTION_STRING")?;
+    let backend = env::var("DB_BACKEND").unwrap_or_else(|_| "oracle".to_string());
+    match backend.as_str() {
+        "oracle" => {
+            let connection_string = env::var("ORACLE_CONNECTION_STRING")?;
+            process_with_sink(DatabaseHandler::new(&connection_string, ColumnMap::from_env()?)?).await
+        }
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "gwsprint.db".to_string());
+            process_with_sink(SqliteHandler::new(&path, ColumnMap::from_env()?)?).await
+        }
+        other => Err(anyhow!("Unknown DB_BACKEND: {}", other)),
+    }
+}
+
+async fn process_with_sink<K: RekeningSink>(db_handler: K) -> Result<()> {
+    let source = env::var("REKENING_SOURCE").unwrap_or_else(|_| "gateway".to_string());
+    match source.as_str() {
+        "gateway" => {
+            let api_config = ApiClientConfig::from_env()?;
+            let token_provider = TokenProvider::from_env()?;
+            let api_client = ApiClient::new(api_config, token_provider)?;
+            run_batch(api_client, db_handler).await
+        }
+        "fixture" => {
+            let fixture_dir = env::var("FIXTURE_DIR")?;
+            run_batch(FixtureSource::new(fixture_dir), db_handler).await
+        }
+        other => Err(anyhow!("Unknown REKENING_SOURCE: {}", other)),
+    }
+}
 
-    let api_client = ApiClient::new(gateway_url, token);
-    let db_handler = DatabaseHandler::new(&connection_string)?;
-    let batch_processor = BatchProcessor::new(api_client, db_handler);
+async fn run_batch<S: RekeningSource, K: RekeningSink>(source: S, db_handler: K) -> Result<()> {
+    let concurrency = env_parse::<usize>("FETCH_CONCURRENCY")?.unwrap_or(5).max(1);
+    let on_circuit_open = env_parse::<CircuitOpenAction>("CIRCUIT_OPEN_ACTION")?
+        .unwrap_or(CircuitOpenAction::Abort);
+    let rules = RecordRules::from_env()?;
+    let defaults = ColumnDefaults::from_env()?;
+    let quarantine = Quarantine::from_env();
+    let batch_processor = BatchProcessor::new(
+        source, db_handler, concurrency, on_circuit_open, rules, defaults, quarantine,
+    );
 
     info!("Starting batch processing for all satkers");
     batch_processor.process_all_satkers().await?;
@@ -29,10 +69,38 @@ async fn process_data() -> Result<()> {
     Ok(())
 }
 
+/// `gwsprint quarantine ...` against the configured DB_BACKEND.
+fn quarantine_command(args: &[String]) -> Result<()> {
+    let backend = env::var("DB_BACKEND").unwrap_or_else(|_| "oracle".to_string());
+    match backend.as_str() {
+        "oracle" => {
+            let connection_string = env::var("ORACLE_CONNECTION_STRING")?;
+            let sink = DatabaseHandler::new(&connection_string, ColumnMap::from_env()?)?;
+            quarantine::run_command(&sink, args)
+        }
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "gwsprint.db".to_string());
+            quarantine::run_command(&SqliteHandler::new(&path, ColumnMap::from_env()?)?, args)
+        }
+        other => Err(anyhow!("Unknown DB_BACKEND: {}", other)),
+    }
+}
+
+/// Runs one batch with a fresh run id attached to every log line.
+async fn run_with_id() -> Result<()> {
+    let run_id = Uuid::new_v4().to_string();
+    logging::RUN_ID.scope(run_id, process_data()).await
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    env_logger::init();
+    logging::init()?;
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("quarantine") {
+        return quarantine_command(&args[1..]);
+    }
 
     let scheduler_enabled = env::var("SCHEDULER_ENABLED")
         .unwrap_or_else(|_| "false".to_string())
@@ -46,7 +114,7 @@ async fn main() -> Result<()> {
         scheduler
             .add(Job::new_async(schedule.as_str(), |_uuid, _l| {
                 Box::pin(async {
-                    if let Err(e) = process_data().await {
+                    if let Err(e) = run_with_id().await {
                         error!("Error processing data: {:?}", e);
                     }
                 })
@@ -57,7 +125,7 @@ async fn main() -> Result<()> {
         tokio::signal::ctrl_c().await?;
         scheduler.shutdown().await?;
     } else {
-        if let Err(e) = process_data().await {
+        if let Err(e) = run_with_id().await {
             error!("Error processing data: {:?}", e);
         }
     }
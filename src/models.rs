@@ -1,18 +1,93 @@
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDateTime;
-use log::{info, error};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use crate::defaults::RecordDefaults;
+use crate::ids::{KdSatker, NoIzin, NoRekening};
+use crate::logging;
+use crate::validation::{RecordRules, ValidationIssue};
+use log::{debug, warn};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "RawRekeningResponse")]
 pub struct RekeningResponse {
     pub success: bool,
     pub message: String,
     pub code: String,
-    #[serde(default)]
     pub data: Vec<RekeningData>,
     pub length: i32,
+    /// Elements of `data` that did not fit `RekeningData`.
+    #[serde(skip)]
+    pub rejects: Vec<RecordReject>,
+}
+
+/// A `data` element that failed to deserialize, kept so the rest of the
+/// satker can still be loaded.
+#[derive(Debug, Clone)]
+pub struct RecordReject {
+    /// Position in the gateway's `data` array, counted across pages.
+    pub index: usize,
+    pub raw: String,
+    pub error: String,
+}
+
+/// Wire shape of the response; `data` is parsed element by element in
+/// the conversion to `RekeningResponse`.
+#[derive(Deserialize)]
+struct RawRekeningResponse {
+    success: bool,
+    message: String,
+    code: String,
+    #[serde(default)]
+    data: Option<Vec<serde_json::Value>>,
+    length: i32,
+}
+
+impl From<RawRekeningResponse> for RekeningResponse {
+    fn from(raw: RawRekeningResponse) -> Self {
+        let mut data = Vec::new();
+        let mut rejects = Vec::new();
+
+        for (index, element) in raw.data.unwrap_or_default().into_iter().enumerate() {
+            let raw_json = element.to_string();
+            match serde_json::from_value::<RekeningData>(element) {
+                Ok(record) => data.push(record),
+                Err(e) => rejects.push(RecordReject {
+                    index,
+                    raw: raw_json,
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        Self {
+            success: raw.success,
+            message: raw.message,
+            code: raw.code,
+            data,
+            length: raw.length,
+            rejects,
+        }
+    }
+}
+
+impl RekeningResponse {
+    /// Elements received, whether or not they deserialized.
+    pub fn received(&self) -> usize {
+        self.data.len() + self.rejects.len()
+    }
+
+    /// Appends a following page, renumbering its rejects to stay unique.
+    pub fn extend_page(&mut self, page: RekeningResponse) {
+        let offset = self.received();
+        self.data.extend(page.data);
+        self.rejects.extend(page.rejects.into_iter().map(|mut reject| {
+            reject.index += offset;
+            reject
+        }));
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct RekeningData {
     pub kdjenis: Option<String>,
     pub nmjenis: Option<String>,
@@ -30,58 +105,271 @@ pub struct RekeningData {
 }
 
 impl RekeningData {
-    pub fn to_rekening(&self) -> Option<Rekening> {
-        // Add validation logging
-        info!("Converting RekeningData to Rekening: {:?}", self);
-        
-        // First validate NOREK since it's the most important field
-        let no_rekening = match &self.norek {
-            Some(norek) if !norek.trim().is_empty() => norek.clone(),
-            _ => {
-                error!("Missing or empty NOREK");
-                return None;
+    /// Normalizes and checks the record and builds the account to load.
+    /// Errors keep it out; warnings travel along in `Rekening::warnings`.
+    pub fn to_rekening(
+        &self,
+        rules: &RecordRules,
+        defaults: &RecordDefaults,
+    ) -> Result<Rekening, Vec<ValidationIssue>> {
+        debug!(event = "convert"; "Converting RekeningData to Rekening: {:?}", self);
+        let (normalized, originals) = rules.normalization.apply(self);
+        let original_values = if originals.is_empty() {
+            None
+        } else {
+            debug!(event = "normalized"; "Normalized fields: {:?}", originals.keys().collect::<Vec<_>>());
+            serde_json::to_string(&originals).ok()
+        };
+        normalized.convert(rules, defaults, original_values)
+    }
+
+    fn convert(
+        &self,
+        rules: &RecordRules,
+        defaults: &RecordDefaults,
+        original_values: Option<String>,
+    ) -> Result<Rekening, Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+
+        let no_rekening = match self.norek.as_deref().filter(|norek| !norek.trim().is_empty()) {
+            Some(norek) => match norek.parse::<NoRekening>() {
+                Ok(no_rekening) => {
+                    rules.validation.check_length("norek", Some(no_rekening.as_str()), &mut issues);
+                    Some(no_rekening)
+                }
+                Err(e) => {
+                    issues.push(ValidationIssue::error("norek", "format", e.to_string()));
+                    None
+                }
+            },
+            None => {
+                issues.push(ValidationIssue::error("norek", "required", "NOREK is missing or empty"));
+                None
             }
         };
 
-        let tgl_izin = match self.tglizin.as_ref().and_then(|date_str| {
-            NaiveDateTime::parse_from_str(date_str, "%Y-%m-%dT%H:%M:%S.%fZ")
-                .ok()
-                .map(|dt| dt.format("%Y-%m-%d").to_string())
-        }) {
-            Some(date) => date,
+        let kd_satker = match self.kdsatker.as_deref().filter(|kdsatker| !kdsatker.trim().is_empty()) {
+            Some(kdsatker) => match kdsatker.parse::<KdSatker>() {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    issues.push(ValidationIssue::error("kdsatker", "format", e.to_string()));
+                    None
+                }
+            },
             None => {
-                error!("Failed to parse tglizin: {:?}", self.tglizin);
-                return None;
+                issues.push(ValidationIssue::error("kdsatker", "required", "kdsatker is missing or empty"));
+                None
             }
         };
 
-        // Validate required fields
-        if self.kdsatker.is_none() {
-            error!("Missing kdsatker");
-            return None;
+        // A missing permit number stays allowed, a malformed one is not
+        let no_izin = match self.noizin.as_deref().filter(|noizin| !noizin.trim().is_empty()) {
+            Some(noizin) => match noizin.parse::<NoIzin>() {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    issues.push(ValidationIssue::error("noizin", "format", e.to_string()));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let tgl_izin = match rules.dates.parse_tglizin(self.tglizin.as_deref()) {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                issues.push(ValidationIssue::error("tglizin", "date", reason));
+                None
+            }
+        };
+        if let Some(parsed) = &tgl_izin {
+            debug!(event = "tglizin_format"; "tglizin parsed with format {}", parsed.format);
+            let today = Utc::now().with_timezone(&rules.dates.timezone).date_naive();
+            if parsed.timestamp.date() > today {
+                issues.push(ValidationIssue::warning("tglizin", "not_future", "tglizin is in the future"));
+            }
         }
 
-        Some(Rekening {
+        rules.validation.check_length("nmrek", self.nmrek.as_deref(), &mut issues);
+        rules.validation.check_length("nmbank", self.nmbank.as_deref(), &mut issues);
+        rules.validation.check_length("noizin", self.noizin.as_deref(), &mut issues);
+        rules.validation.check_length("nmcabang", self.nmcabang.as_deref(), &mut issues);
+
+        let (Some(no_rekening), Some(kd_satker)) = (no_rekening, kd_satker) else {
+            return Err(issues);
+        };
+        if issues.iter().any(ValidationIssue::is_error) {
+            return Err(issues);
+        }
+
+        let status_rekening = rules.statuses.resolve(self.kdstatus.as_deref());
+        if status_rekening.is_none() {
+            warn!(event = "status_unknown"; "Unknown kdstatus {:?} ({:?}), account goes to review",
+                  self.kdstatus, self.nmstatus);
+        }
+
+        Ok(Rekening {
             kdjenis: self.kdjenis.clone().unwrap_or_default(),
-            kd_satker: self.kdsatker.clone()?,
+            kd_satker,
             nama_bank: self.nmbank.clone().unwrap_or_default(),
             nama_rekening: self.nmrek.clone().unwrap_or_default(),
-            no_izin: self.noizin.clone().unwrap_or_default(),
+            no_izin,
             no_rekening,
-            tgl_izin,
+            tgl_izin: tgl_izin.as_ref().map(|parsed| parsed.timestamp.date()),
+            tgl_izin_timestamp: tgl_izin.as_ref().map(|parsed| parsed.timestamp),
+            tgl_izin_format: tgl_izin.map(|parsed| parsed.format),
             desc_status_rekening: self.nmstatus.clone().unwrap_or_default(),
+            status_rekening,
+            owner: defaults.owner.clone(),
+            kode_unit_teknis: defaults.kode_unit_teknis.clone(),
+            mata_uang: defaults.mata_uang.clone(),
+            audit_user: defaults.audit_user.clone(),
+            nama_jenis: self.nmjenis.clone().unwrap_or_default(),
+            kode_bank: self.kdbank.clone().unwrap_or_default(),
+            kode_jenis_bank: self.kdjenbank.clone().unwrap_or_default(),
+            nama_cabang: self.nmcabang.clone().unwrap_or_default(),
+            kode_status: self.kdstatus.clone().unwrap_or_default(),
+            original_values,
+            warnings: issues,
         })
     }
 }
 
-#[derive(Debug)]
 pub struct Rekening {
     pub kdjenis: String,           // Used as KODE
-    pub kd_satker: String,         // KODE_SATKER
+    pub kd_satker: KdSatker,       // KODE_SATKER
     pub nama_bank: String,         // NAMA_BANK
     pub nama_rekening: String,     // NAMA_REK
-    pub no_izin: String,          // NO_IZIN
-    pub no_rekening: String,      // NOREK
-    pub tgl_izin: String,         // TGL_IZIN
+    pub no_izin: Option<NoIzin>,  // NO_IZIN, empty when the gateway has none
+    pub no_rekening: NoRekening,  // NOREK
+    pub tgl_izin: Option<NaiveDate>, // TGL_IZIN, local date in DateConfig's zone
+    pub tgl_izin_timestamp: Option<NaiveDateTime>, // optional full timestamp column, see ColumnMap
+    pub tgl_izin_format: Option<String>, // format that matched, `default` from the null policy
     pub desc_status_rekening: String, // DESC_STATUS_REKENING
-} 
\ No newline at end of file
+    pub status_rekening: Option<i64>, // STATUS_REKENING, None when kdstatus is not in StatusMap
+    pub owner: String,            // OWNER
+    pub kode_unit_teknis: String, // KODE_UNIT_TEKNIS
+    pub mata_uang: String,        // MATA_UANG
+    pub audit_user: String,       // CREATED_BY / MODIFIED_BY
+    // Target columns of the fields below are configurable, see ColumnMap
+    pub nama_jenis: String,       // nmjenis
+    pub kode_bank: String,        // kdbank
+    pub kode_jenis_bank: String,  // kdjenbank
+    pub nama_cabang: String,      // nmcabang
+    pub kode_status: String,      // kdstatus
+    /// JSON object of the gateway values normalization changed, by field.
+    pub original_values: Option<String>,
+    /// Non-fatal issues found while converting.
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl Rekening {
+    /// Why an account without a STATUS_REKENING goes to review.
+    pub fn review_reason(&self) -> String {
+        match self.kode_status.as_str() {
+            "" => "Missing kdstatus".to_string(),
+            kdstatus => format!("Unknown kdstatus {:?}", kdstatus),
+        }
+    }
+}
+
+// Debug is hand-written for both record types so that account numbers,
+// holder names and permit numbers never reach the logs unmasked
+
+impl fmt::Debug for RekeningData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RekeningData")
+            .field("kdjenis", &self.kdjenis)
+            .field("nmjenis", &self.nmjenis)
+            .field("kdsatker", &self.kdsatker)
+            .field("kdbank", &self.kdbank)
+            .field("kdjenbank", &self.kdjenbank)
+            .field("nmbank", &self.nmbank)
+            .field("nmcabang", &self.nmcabang)
+            .field("nmrek", &logging::opt_pii(&self.nmrek))
+            .field("norek", &logging::opt_norek(&self.norek))
+            .field("noizin", &logging::opt_pii(&self.noizin))
+            .field("tglizin", &self.tglizin)
+            .field("kdstatus", &self.kdstatus)
+            .field("nmstatus", &self.nmstatus)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Rekening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rekening")
+            .field("kdjenis", &self.kdjenis)
+            .field("kd_satker", &self.kd_satker)
+            .field("nama_bank", &self.nama_bank)
+            .field("nama_rekening", &logging::pii(&self.nama_rekening))
+            .field("no_izin", &self.no_izin)
+            .field("no_rekening", &self.no_rekening)
+            .field("tgl_izin", &self.tgl_izin)
+            .field("tgl_izin_timestamp", &self.tgl_izin_timestamp)
+            .field("tgl_izin_format", &self.tgl_izin_format)
+            .field("desc_status_rekening", &self.desc_status_rekening)
+            .field("status_rekening", &self.status_rekening)
+            .field("owner", &self.owner)
+            .field("kode_unit_teknis", &self.kode_unit_teknis)
+            .field("mata_uang", &self.mata_uang)
+            .field("audit_user", &self.audit_user)
+            .field("nama_jenis", &self.nama_jenis)
+            .field("kode_bank", &self.kode_bank)
+            .field("kode_jenis_bank", &self.kode_jenis_bank)
+            .field("nama_cabang", &self.nama_cabang)
+            .field("kode_status", &self.kode_status)
+            .field("original_values", &logging::opt_pii(&self.original_values))
+            .field("warnings", &self.warnings)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> RekeningResponse {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn malformed_elements_are_rejected_one_by_one() {
+        let response = parse(
+            r#"{"success":true,"message":"ok","code":"00","length":3,"data":[
+                {"kdsatker":"123456","norek":"111"},
+                {"kdsatker":"123456","norek":42},
+                {"kdsatker":"123456","norek":"333"}]}"#,
+        );
+
+        assert_eq!(response.data.len(), 2);
+        assert_eq!(response.data[1].norek.as_deref(), Some("333"));
+        assert_eq!(response.rejects.len(), 1);
+        assert_eq!(response.rejects[0].index, 1);
+        assert!(response.rejects[0].raw.contains("42"));
+        assert_eq!(response.received(), 3);
+    }
+
+    #[test]
+    fn missing_or_null_data_is_empty() {
+        let missing = parse(r#"{"success":false,"message":"no data","code":"01","length":0}"#);
+        let null = parse(r#"{"success":true,"message":"ok","code":"00","length":0,"data":null}"#);
+        assert_eq!(missing.received(), 0);
+        assert_eq!(null.received(), 0);
+    }
+
+    #[test]
+    fn extend_page_renumbers_rejects_across_pages() {
+        let mut first = parse(
+            r#"{"success":true,"message":"ok","code":"00","length":4,"data":[
+                {"norek":"111"},{"norek":false}]}"#,
+        );
+        let second = parse(
+            r#"{"success":true,"message":"ok","code":"00","length":4,"data":[
+                {"norek":[]},{"norek":"444"}]}"#,
+        );
+        first.extend_page(second);
+
+        assert_eq!(first.received(), 4);
+        assert_eq!(first.rejects.iter().map(|reject| reject.index).collect::<Vec<_>>(), [1, 2]);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::models::RekeningData;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use unicode_normalization::UnicodeNormalization;
+
+/// Used when NORMALIZE is not set. norek_digits changes the MERGE key of
+/// accounts loaded without it; sql/oracle/006 brings those in line.
+const DEFAULT_STEPS: &str = "trim,nfc,whitespace,norek_digits,bank_alias";
+
+/// One normalization step, applied in the order below whatever the order
+/// in NORMALIZE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Trim,
+    /// Unicode NFC, so composed and decomposed accents compare equal.
+    Nfc,
+    /// Runs of whitespace become a single space.
+    Whitespace,
+    /// Drops `-`, `.` and spaces from NOREK when only digits remain.
+    NorekDigits,
+    /// Replaces a known bank name alias with its canonical name.
+    BankAlias,
+}
+
+impl std::str::FromStr for Step {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "trim" => Ok(Step::Trim),
+            "nfc" => Ok(Step::Nfc),
+            "whitespace" => Ok(Step::Whitespace),
+            "norek_digits" => Ok(Step::NorekDigits),
+            "bank_alias" => Ok(Step::BankAlias),
+            other => Err(anyhow!(
+                "expected trim, nfc, whitespace, norek_digits or bank_alias, got {}", other
+            )),
+        }
+    }
+}
+
+/// Cleans up gateway strings before they are validated and mapped.
+#[derive(Debug, Clone)]
+pub struct Normalization {
+    steps: Vec<Step>,
+    /// Lowercased alias to canonical bank name; canonical names map to
+    /// themselves so differently cased spellings are fixed too.
+    bank_aliases: HashMap<String, String>,
+}
+
+impl Normalization {
+    /// Reads NORMALIZE (comma separated steps, empty for none) and
+    /// BANK_ALIASES (comma separated `alias=canonical` pairs, matched
+    /// case-insensitively after the other steps).
+    pub fn from_env() -> Result<Self> {
+        let steps = env::var("NORMALIZE")
+            .unwrap_or_else(|_| DEFAULT_STEPS.to_string())
+            .split(',')
+            .filter(|step| !step.trim().is_empty())
+            .map(|step| step.parse::<Step>().map_err(|e| anyhow!("Invalid NORMALIZE entry: {}", e)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut bank_aliases = HashMap::new();
+        let spec = env::var("BANK_ALIASES").unwrap_or_default();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (alias, canonical) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid BANK_ALIASES entry {:?}, expected alias=canonical", entry))?;
+            let canonical = canonical.trim().to_string();
+            if let Some(previous) = bank_aliases.insert(alias.trim().to_lowercase(), canonical.clone()) {
+                if previous != canonical {
+                    return Err(anyhow!("Bank alias {:?} maps to both {:?} and {:?}", alias.trim(), previous, canonical));
+                }
+            }
+            bank_aliases.entry(canonical.to_lowercase()).or_insert(canonical);
+        }
+
+        Ok(Self { steps, bank_aliases })
+    }
+
+    fn has(&self, step: Step) -> bool {
+        self.steps.contains(&step)
+    }
+
+    fn normalize(&self, field: &str, value: &str) -> String {
+        let mut value = value.to_string();
+        if self.has(Step::Trim) {
+            value = value.trim().to_string();
+        }
+        if self.has(Step::Nfc) {
+            value = value.nfc().collect();
+        }
+        if self.has(Step::Whitespace) {
+            value = collapse_whitespace(&value);
+        }
+        if field == "norek" && self.has(Step::NorekDigits) {
+            let digits = value.chars().filter(|c| !matches!(c, '-' | '.') && !c.is_whitespace()).collect::<String>();
+            // Anything else is left for validation to reject
+            if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                value = digits;
+            }
+        }
+        if field == "nmbank" && self.has(Step::BankAlias) {
+            if let Some(canonical) = self.bank_aliases.get(&value.to_lowercase()) {
+                value = canonical.clone();
+            }
+        }
+        value
+    }
+
+    /// The normalized record and the original of every field it changed,
+    /// keyed by gateway field name.
+    pub fn apply(&self, data: &RekeningData) -> (RekeningData, BTreeMap<&'static str, String>) {
+        let mut originals = BTreeMap::new();
+        let mut field = |name: &'static str, value: &Option<String>| {
+            let original = value.as_deref()?;
+            let normalized = self.normalize(name, original);
+            if normalized != original {
+                originals.insert(name, original.to_string());
+            }
+            Some(normalized)
+        };
+
+        let normalized = RekeningData {
+            kdjenis: field("kdjenis", &data.kdjenis),
+            nmjenis: field("nmjenis", &data.nmjenis),
+            kdsatker: field("kdsatker", &data.kdsatker),
+            kdbank: field("kdbank", &data.kdbank),
+            kdjenbank: field("kdjenbank", &data.kdjenbank),
+            nmbank: field("nmbank", &data.nmbank),
+            nmcabang: field("nmcabang", &data.nmcabang),
+            nmrek: field("nmrek", &data.nmrek),
+            norek: field("norek", &data.norek),
+            noizin: field("noizin", &data.noizin),
+            tglizin: field("tglizin", &data.tglizin),
+            kdstatus: field("kdstatus", &data.kdstatus),
+            nmstatus: field("nmstatus", &data.nmstatus),
+        };
+        (normalized, originals)
+    }
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    let mut collapsed = String::with_capacity(value.len());
+    let mut in_whitespace = false;
+    for c in value.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalization(steps: &[Step]) -> Normalization {
+        let bank_aliases = [("bri", "BANK BRI"), ("bank rakyat indonesia", "BANK BRI"), ("bank bri", "BANK BRI")]
+            .into_iter()
+            .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+            .collect();
+        Normalization { steps: steps.to_vec(), bank_aliases }
+    }
+
+    fn all_steps() -> Normalization {
+        normalization(&[Step::Trim, Step::Nfc, Step::Whitespace, Step::NorekDigits, Step::BankAlias])
+    }
+
+    #[test]
+    fn normalizes_fields_and_keeps_changed_originals() {
+        let data = RekeningData {
+            kdsatker: Some("123456".to_string()),
+            nmrek: Some("  BPP   Kantor\tPusat ".to_string()),
+            norek: Some("0123-45.67 8".to_string()),
+            nmbank: Some("Bank  Rakyat Indonesia".to_string()),
+            ..Default::default()
+        };
+        let (normalized, originals) = all_steps().apply(&data);
+
+        assert_eq!(normalized.kdsatker.as_deref(), Some("123456"));
+        assert_eq!(normalized.nmrek.as_deref(), Some("BPP Kantor Pusat"));
+        assert_eq!(normalized.norek.as_deref(), Some("012345678"));
+        assert_eq!(normalized.nmbank.as_deref(), Some("BANK BRI"));
+        assert!(normalized.noizin.is_none());
+        assert_eq!(originals.keys().copied().collect::<Vec<_>>(), ["nmbank", "nmrek", "norek"]);
+        assert_eq!(originals["norek"], "0123-45.67 8");
+    }
+
+    #[test]
+    fn composes_decomposed_accents() {
+        let data = RekeningData { nmcabang: Some("Cabang Se\u{301}mau".to_string()), ..Default::default() };
+        let (normalized, originals) = all_steps().apply(&data);
+        assert_eq!(normalized.nmcabang.as_deref(), Some("Cabang S\u{e9}mau"));
+        assert!(originals.contains_key("nmcabang"));
+    }
+
+    #[test]
+    fn leaves_invalid_norek_for_validation() {
+        let data = RekeningData { norek: Some("12-AB".to_string()), ..Default::default() };
+        let (normalized, originals) = all_steps().apply(&data);
+        assert_eq!(normalized.norek.as_deref(), Some("12-AB"));
+        assert!(originals.is_empty());
+    }
+
+    #[test]
+    fn only_configured_steps_run() {
+        let data = RekeningData {
+            nmrek: Some(" a  b ".to_string()),
+            norek: Some("12-34".to_string()),
+            nmbank: Some("bri".to_string()),
+            ..Default::default()
+        };
+        let (normalized, originals) = normalization(&[Step::Trim]).apply(&data);
+        assert_eq!(normalized.nmrek.as_deref(), Some("a  b"));
+        assert_eq!(normalized.norek.as_deref(), Some("12-34"));
+        assert_eq!(normalized.nmbank.as_deref(), Some("bri"));
+        assert_eq!(originals.len(), 1);
+
+        let (normalized, originals) = normalization(&[]).apply(&data);
+        assert_eq!(normalized.nmrek.as_deref(), Some(" a  b "));
+        assert!(originals.is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+use crate::batch_processor::TRANSACTION_BATCH_SIZE;
+use crate::defaults::{ColumnDefaults, RecordDefaults, SatkerAttributes};
+use crate::ids::KdSatker;
+use crate::logging::{self, RUN_ID};
+use crate::models::RekeningData;
+use crate::sink::RekeningSink;
+use crate::validation::{RecordRules, ValidationIssue};
+use anyhow::{anyhow, Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// A gateway record that could not be loaded, kept with enough context to
+/// load it later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedRecord {
+    pub kd_satker: KdSatker,
+    /// SHA-256 of `raw`; together with the satker it identifies the record,
+    /// so quarantining it again on the next run updates the same row.
+    pub record_hash: String,
+    /// The `data` element as JSON.
+    pub raw: String,
+    pub reason: String,
+    pub run_id: String,
+    /// UTC.
+    pub quarantined_at: NaiveDateTime,
+}
+
+impl QuarantinedRecord {
+    /// Stamps the record with the current run id, if any, and time.
+    pub fn new(kd_satker: &KdSatker, raw: String, reason: String) -> Self {
+        let record_hash = Sha256::digest(raw.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Self {
+            kd_satker: kd_satker.clone(),
+            record_hash,
+            raw,
+            reason,
+            run_id: RUN_ID.try_with(|id| id.clone()).unwrap_or_default(),
+            quarantined_at: Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn from_data(kd_satker: &KdSatker, data: &RekeningData, reason: String) -> Result<Self> {
+        Ok(Self::new(kd_satker, serde_json::to_string(data)?, reason))
+    }
+}
+
+/// Reject reason for records that failed validation: the errors only, as
+/// warnings did not keep the record out.
+pub fn validation_reason(issues: &[ValidationIssue]) -> String {
+    issues
+        .iter()
+        .filter(|issue| issue.is_error())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Where rejected records go: the sink's quarantine table, or a JSON lines
+/// file when the table cannot be written.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    file: PathBuf,
+}
+
+impl Quarantine {
+    /// Reads QUARANTINE_FILE (default `quarantine.jsonl`).
+    pub fn from_env() -> Self {
+        let file = env::var("QUARANTINE_FILE").unwrap_or_else(|_| "quarantine.jsonl".to_string());
+        Self { file: PathBuf::from(file) }
+    }
+
+    /// Writes the record in `tx`, so it is kept or dropped together with
+    /// the rest of the batch. Falls back to the file if the table fails;
+    /// the file is not transactional, but a record quarantined again
+    /// replaces its earlier entry, so a rolled back batch only leaves
+    /// behind what the next run would store anyway.
+    pub fn store<K: RekeningSink>(&self, sink: &K, tx: &K::Transaction, record: &QuarantinedRecord) -> Result<()> {
+        match sink.quarantine(tx, record) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                warn!(event = "quarantine_fallback";
+                      "Failed to quarantine record for satker {} in the database, writing it to {}: {:?}",
+                      record.kd_satker, self.file.display(), e);
+                self.append(record)
+            }
+        }
+    }
+
+    fn append(&self, record: &QuarantinedRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)
+            .with_context(|| format!("Failed to open quarantine file {}", self.file.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// One entry per satker and record hash, like the table: appending
+    /// keeps concurrent writers safe, so a later line for the same record
+    /// replaces the earlier one here.
+    fn read_file(&self) -> Result<Vec<QuarantinedRecord>> {
+        let file = match fs::File::open(&self.file) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to open {}", self.file.display()))),
+        };
+
+        let mut records: Vec<QuarantinedRecord> = Vec::new();
+        for (idx, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: QuarantinedRecord = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid line {} in {}", idx + 1, self.file.display()))?;
+            match records
+                .iter_mut()
+                .find(|existing| existing.kd_satker == record.kd_satker && existing.record_hash == record.record_hash)
+            {
+                Some(existing) => *existing = record,
+                None => records.push(record),
+            }
+        }
+        Ok(records)
+    }
+
+    fn write_file(&self, records: &[QuarantinedRecord]) -> Result<()> {
+        if records.is_empty() {
+            return match fs::remove_file(&self.file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let mut lines = String::new();
+        for record in records {
+            lines.push_str(&serde_json::to_string(record)?);
+            lines.push('\n');
+        }
+        fs::write(&self.file, lines)?;
+        Ok(())
+    }
+
+    /// Table rows first, then file entries, optionally for one satker.
+    pub fn list<K: RekeningSink>(&self, sink: &K, kd_satker: Option<&KdSatker>) -> Result<Vec<Entry>> {
+        let mut entries = sink
+            .list_quarantined(kd_satker)?
+            .into_iter()
+            .map(|record| Entry { record, in_file: false })
+            .collect::<Vec<_>>();
+        entries.extend(
+            self.read_file()?
+                .into_iter()
+                .filter(|record| kd_satker.is_none_or(|kd| &record.kd_satker == kd))
+                .map(|record| Entry { record, in_file: true }),
+        );
+        Ok(entries)
+    }
+}
+
+/// A quarantined record and where it is stored.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub record: QuarantinedRecord,
+    pub in_file: bool,
+}
+
+/// What re-validating a quarantined record found, or did with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verdict {
+    Loaded,
+    Review,
+    /// Still fails to parse, validate or write; the reason is updated.
+    Rejected,
+}
+
+impl Verdict {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Loaded => "loaded",
+            Verdict::Review => "review",
+            Verdict::Rejected => "rejected",
+        }
+    }
+}
+
+/// `gwsprint quarantine <list|revalidate|resubmit> [kd_satker]`.
+///
+/// `revalidate` runs the current mapping and validation rules over the
+/// quarantined records without writing anything; `resubmit` also loads
+/// the records that now pass and removes them from the quarantine.
+pub fn run_command<K: RekeningSink>(sink: &K, args: &[String]) -> Result<()> {
+    let quarantine = Quarantine::from_env();
+    let kd_satker = args
+        .get(1)
+        .map(|kd_satker| kd_satker.parse::<KdSatker>())
+        .transpose()
+        .map_err(|e| anyhow!("Invalid satker argument: {}", e))?;
+    let kd_satker = kd_satker.as_ref();
+
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for entry in quarantine.list(sink, kd_satker)? {
+                println!("{}", describe(&entry));
+            }
+            Ok(())
+        }
+        Some("revalidate") => resubmit(&quarantine, sink, kd_satker, false),
+        Some("resubmit") => resubmit(&quarantine, sink, kd_satker, true),
+        _ => Err(anyhow!("usage: gwsprint quarantine <list|revalidate|resubmit> [kd_satker]")),
+    }
+}
+
+/// One line per entry; the raw record is left out as it holds PII.
+fn describe(entry: &Entry) -> String {
+    let record = &entry.record;
+    let norek = serde_json::from_str::<RekeningData>(&record.raw)
+        .ok()
+        .and_then(|data| data.norek)
+        .map(|norek| logging::norek(&norek).to_string())
+        .unwrap_or_else(|| "-".to_string());
+
+    format!(
+        "{} {} {} {} norek={} run={} {}",
+        record.quarantined_at.format("%Y-%m-%d %H:%M:%S"),
+        record.kd_satker,
+        short_hash(record),
+        if entry.in_file { "file" } else { "table" },
+        norek,
+        record.run_id,
+        record.reason,
+    )
+}
+
+fn resubmit<K: RekeningSink>(quarantine: &Quarantine, sink: &K, kd_satker: Option<&KdSatker>, write: bool) -> Result<()> {
+    let rules = RecordRules::from_env()?;
+    let defaults = ColumnDefaults::from_env()?;
+
+    let mut by_satker: BTreeMap<KdSatker, Vec<Entry>> = BTreeMap::new();
+    for entry in quarantine.list(sink, kd_satker)? {
+        by_satker.entry(entry.record.kd_satker.clone()).or_default().push(entry);
+    }
+
+    let mut verdicts: BTreeMap<Verdict, usize> = BTreeMap::new();
+    let mut kept_in_file = Vec::new();
+
+    for (satker, entries) in by_satker {
+        let attributes = if defaults.per_satker() {
+            sink.satker_attributes(&satker)?
+        } else {
+            SatkerAttributes::default()
+        };
+        let defaults = defaults.for_satker(attributes);
+
+        if !write {
+            for entry in &entries {
+                let (verdict, reason) = revalidate(sink, None, &rules, &defaults, &entry.record);
+                report(&entry.record, verdict, reason.as_deref());
+                *verdicts.entry(verdict).or_default() += 1;
+            }
+            continue;
+        }
+
+        let tx = sink.begin_transaction()?;
+        let resubmitted = resubmit_satker(sink, &tx, &rules, &defaults, entries, &mut verdicts);
+        match resubmitted {
+            Ok(kept) => {
+                sink.commit(&tx)?;
+                kept_in_file.extend(kept);
+            }
+            Err(e) => {
+                let _ = sink.rollback(&tx);
+                return Err(e.context(format!("Failed to resubmit quarantined records for satker {}", satker)));
+            }
+        }
+    }
+
+    if write {
+        // File entries outside the satker filter were not looked at
+        kept_in_file.extend(
+            quarantine
+                .read_file()?
+                .into_iter()
+                .filter(|record| kd_satker.is_some_and(|kd| &record.kd_satker != kd)),
+        );
+        quarantine.write_file(&kept_in_file)?;
+    }
+
+    info!(event = "quarantine_summary"; "Quarantine {}: {:?}",
+          if write { "resubmit" } else { "revalidate" }, verdicts);
+    Ok(())
+}
+
+/// Loads what now passes, releases it from the table and updates the reason
+/// of what does not. Returns the file entries that stay quarantined.
+fn resubmit_satker<K: RekeningSink>(
+    sink: &K,
+    tx: &K::Transaction,
+    rules: &RecordRules,
+    defaults: &RecordDefaults,
+    entries: Vec<Entry>,
+    verdicts: &mut BTreeMap<Verdict, usize>,
+) -> Result<Vec<QuarantinedRecord>> {
+    let mut kept_in_file = Vec::new();
+    let mut pending = 0;
+
+    for Entry { mut record, in_file } in entries {
+        let (verdict, reason) = revalidate(sink, Some(tx), rules, defaults, &record);
+        report(&record, verdict, reason.as_deref());
+        *verdicts.entry(verdict).or_default() += 1;
+
+        if let Some(reason) = reason {
+            record.reason = reason;
+        }
+        match (verdict, in_file) {
+            (Verdict::Rejected, true) => kept_in_file.push(record),
+            (Verdict::Rejected, false) => sink.quarantine(tx, &record)?,
+            (_, true) => {}
+            (_, false) => sink.release_quarantined(tx, &record)?,
+        }
+
+        pending += 1;
+        if pending >= TRANSACTION_BATCH_SIZE {
+            sink.checkpoint(tx)?;
+            pending = 0;
+        }
+    }
+
+    Ok(kept_in_file)
+}
+
+fn report(record: &QuarantinedRecord, verdict: Verdict, reason: Option<&str>) {
+    info!(event = "quarantine_revalidated", verdict = verdict.as_str();
+          "Quarantined record {} for satker {}: {}{}",
+          short_hash(record), record.kd_satker, verdict.as_str(),
+          reason.map(|reason| format!(" ({})", reason)).unwrap_or_default());
+}
+
+fn short_hash(record: &QuarantinedRecord) -> &str {
+    &record.record_hash[..12.min(record.record_hash.len())]
+}
+
+/// Runs one record through parsing, validation and, with `tx`, the sink.
+/// The reason is set when the record is still rejected.
+fn revalidate<K: RekeningSink>(
+    sink: &K,
+    tx: Option<&K::Transaction>,
+    rules: &RecordRules,
+    defaults: &RecordDefaults,
+    record: &QuarantinedRecord,
+) -> (Verdict, Option<String>) {
+    let data = match serde_json::from_str::<RekeningData>(&record.raw) {
+        Ok(data) => data,
+        Err(e) => return (Verdict::Rejected, Some(format!("Malformed record: {}", e))),
+    };
+    let rekening = match data.to_rekening(rules, defaults) {
+        Ok(rekening) => rekening,
+        Err(issues) => return (Verdict::Rejected, Some(validation_reason(&issues))),
+    };
+    let verdict = match rekening.status_rekening {
+        Some(_) => Verdict::Loaded,
+        None => Verdict::Review,
+    };
+    let Some(tx) = tx else {
+        return (verdict, None);
+    };
+
+    let written = match verdict {
+        Verdict::Review => sink.queue_for_review(tx, &rekening, &rekening.review_reason()),
+        _ => sink.upsert_rekening(tx, &rekening).map(|_| ()),
+    };
+    match written {
+        Ok(()) => (verdict, None),
+        Err(e) => {
+            error!(event = "record_failed"; "Failed to resubmit {}: {:?}", logging::norek(rekening.no_rekening.as_str()), e);
+            (Verdict::Rejected, Some(format!("Write failed: {}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::ColumnMap;
+    use crate::sqlite_db::SqliteHandler;
+    use rusqlite::Connection;
+
+    /// A SQLite sink and a quarantine file in a fresh directory, removed
+    /// on drop.
+    struct Scratch {
+        dir: PathBuf,
+        sink: SqliteHandler,
+        quarantine: Quarantine,
+    }
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("gwsprint-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            let db = dir.join("gwsprint.db");
+            let sink = SqliteHandler::new(db.to_str().unwrap(), ColumnMap::from_env().unwrap()).unwrap();
+            let quarantine = Quarantine { file: dir.join("quarantine.jsonl") };
+            Self { dir, sink, quarantine }
+        }
+
+        fn store_in_table(&self, record: &QuarantinedRecord) {
+            let tx = self.sink.begin_transaction().unwrap();
+            self.sink.quarantine(&tx, record).unwrap();
+            self.sink.commit(&tx).unwrap();
+        }
+
+        fn loaded(&self) -> i64 {
+            Connection::open(self.dir.join("gwsprint.db"))
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT", [], |row| row.get(0))
+                .unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn satker(code: &str) -> KdSatker {
+        code.parse().unwrap()
+    }
+
+    /// A record that passes the default rules.
+    fn valid(kd_satker: &str, norek: &str) -> QuarantinedRecord {
+        let raw = format!(
+            r#"{{"kdjenis":"1","kdsatker":"{}","nmbank":"BNI","nmrek":"BPP","norek":"{}","noizin":"S-1","tglizin":"2023-02-01","kdstatus":"1"}}"#,
+            kd_satker, norek
+        );
+        QuarantinedRecord::new(&satker(kd_satker), raw, "Upsert failed: locked".to_string())
+    }
+
+    fn malformed(kd_satker: &str) -> QuarantinedRecord {
+        QuarantinedRecord::new(&satker(kd_satker), r#"{"norek":42}"#.to_string(), "Malformed record".to_string())
+    }
+
+    #[test]
+    fn list_covers_table_and_file_and_filters_by_satker() {
+        let scratch = Scratch::new();
+        scratch.store_in_table(&valid("123456", "111"));
+        scratch.quarantine.append(&malformed("123456")).unwrap();
+        scratch.quarantine.append(&malformed("654321")).unwrap();
+
+        let all = scratch.quarantine.list(&scratch.sink, None).unwrap();
+        assert_eq!(all.iter().map(|entry| entry.in_file).collect::<Vec<_>>(), [false, true, true]);
+
+        let one = scratch.quarantine.list(&scratch.sink, Some(&satker("654321"))).unwrap();
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].record.kd_satker, satker("654321"));
+    }
+
+    #[test]
+    fn file_keeps_one_entry_per_record() {
+        let scratch = Scratch::new();
+        let mut record = malformed("123456");
+        scratch.quarantine.append(&record).unwrap();
+        record.reason = "Malformed record, again".to_string();
+        scratch.quarantine.append(&record).unwrap();
+
+        let records = scratch.quarantine.read_file().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].reason, "Malformed record, again");
+    }
+
+    #[test]
+    fn revalidate_writes_nothing() {
+        let scratch = Scratch::new();
+        scratch.store_in_table(&valid("123456", "111"));
+        scratch.quarantine.append(&valid("123456", "222")).unwrap();
+
+        resubmit(&scratch.quarantine, &scratch.sink, None, false).unwrap();
+
+        assert_eq!(scratch.loaded(), 0);
+        assert_eq!(scratch.quarantine.list(&scratch.sink, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn resubmit_loads_what_passes_and_keeps_the_rest() {
+        let scratch = Scratch::new();
+        scratch.store_in_table(&valid("123456", "111"));
+        scratch.store_in_table(&malformed("123456"));
+        scratch.quarantine.append(&valid("123456", "222")).unwrap();
+        scratch.quarantine.append(&malformed("123456")).unwrap();
+
+        resubmit(&scratch.quarantine, &scratch.sink, None, true).unwrap();
+
+        assert_eq!(scratch.loaded(), 2);
+        let left = scratch.quarantine.list(&scratch.sink, None).unwrap();
+        assert_eq!(left.iter().map(|entry| entry.in_file).collect::<Vec<_>>(), [false, true]);
+        assert!(left.iter().all(|entry| entry.record.reason.starts_with("Malformed record: ")));
+    }
+
+    #[test]
+    fn resubmit_for_one_satker_keeps_other_file_entries() {
+        let scratch = Scratch::new();
+        scratch.quarantine.append(&valid("123456", "111")).unwrap();
+        scratch.quarantine.append(&valid("654321", "222")).unwrap();
+
+        resubmit(&scratch.quarantine, &scratch.sink, Some(&satker("123456")), true).unwrap();
+
+        assert_eq!(scratch.loaded(), 1);
+        let left = scratch.quarantine.read_file().unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].kd_satker, satker("654321"));
+    }
+}
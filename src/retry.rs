@@ -0,0 +1,210 @@
+use crate::config::{env_millis, env_parse};
+use crate::gateway_error::GatewayError;
+use crate::tls::PinMismatch;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use tokio::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads FETCH_MAX_ATTEMPTS, FETCH_BACKOFF_BASE_MS and FETCH_BACKOFF_MAX_MS,
+    /// falling back to the defaults for any variable that is not set.
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+
+        Ok(Self {
+            max_attempts: env_parse::<u32>("FETCH_MAX_ATTEMPTS")?
+                .unwrap_or(defaults.max_attempts)
+                .max(1),
+            base_delay: env_millis("FETCH_BACKOFF_BASE_MS")?.unwrap_or(defaults.base_delay),
+            max_delay: env_millis("FETCH_BACKOFF_MAX_MS")?.unwrap_or(defaults.max_delay),
+        })
+    }
+
+    /// Delay before the next attempt. `attempt` is the 1-based number of the
+    /// attempt that just failed. A server supplied Retry-After wins over the
+    /// computed backoff and is honored in full; when it is longer than
+    /// `max_delay` there is no retry, as an earlier one would only be
+    /// refused again. The computed backoff is capped at `max_delay`.
+    pub fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(delay) = retry_after {
+            return (delay <= self.max_delay).then_some(delay);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(16);
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        // Equal jitter: keep half of the backoff, randomize the other half
+        let half = backoff / 2;
+        let jitter_ms = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        Some(half + Duration::from_millis(jitter_ms))
+    }
+}
+
+/// Outcome of a single gateway call, split by whether another attempt could
+/// succeed.
+#[derive(Debug)]
+pub enum FetchError {
+    Retryable {
+        error: anyhow::Error,
+        retry_after: Option<Duration>,
+    },
+    /// 401 from the gateway; worth one more try after refreshing the token.
+    Unauthorized(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl FetchError {
+    pub fn from_reqwest(error: reqwest::Error) -> Self {
+        // Another attempt would meet the same certificate
+        if PinMismatch::caused(&error) {
+            return FetchError::Permanent(error.into());
+        }
+        if error.is_connect() || error.is_timeout() || error.is_body() {
+            FetchError::Retryable {
+                error: error.into(),
+                retry_after: None,
+            }
+        } else {
+            FetchError::Permanent(error.into())
+        }
+    }
+
+    pub fn from_status(
+        status: StatusCode,
+        headers: &HeaderMap,
+        gateway_error: GatewayError,
+        kd_satker: &str,
+    ) -> Self {
+        let error = anyhow::Error::new(gateway_error)
+            .context(format!("Gateway returned {} for satker {}", status, kd_satker));
+        match status {
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => FetchError::Retryable {
+                error,
+                retry_after: parse_retry_after(headers),
+            },
+            StatusCode::UNAUTHORIZED => FetchError::Unauthorized(error),
+            _ => FetchError::Permanent(error),
+        }
+    }
+
+    pub fn into_inner(self) -> anyhow::Error {
+        match self {
+            FetchError::Retryable { error, .. } => error,
+            FetchError::Unauthorized(error) => error,
+            FetchError::Permanent(error) => error,
+        }
+    }
+}
+
+/// Retry-After is either a number of seconds or an HTTP date.
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    (at - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn backoff_grows_with_jitter_and_is_capped() {
+        let policy = policy();
+        for (attempt, full) in [(1, 100), (2, 200), (3, 400), (10, 1000)] {
+            let delay = policy.delay_for(attempt, None).unwrap();
+            let full = Duration::from_millis(full);
+            assert!(delay >= full / 2 && delay <= full, "attempt {}: {:?}", attempt, delay);
+        }
+    }
+
+    #[test]
+    fn retry_after_is_honored_in_full() {
+        assert_eq!(policy().delay_for(1, Some(Duration::from_millis(700))), Some(Duration::from_millis(700)));
+        assert_eq!(policy().delay_for(1, Some(Duration::from_secs(1))), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn retry_after_beyond_the_cap_stops_retrying() {
+        assert_eq!(policy().delay_for(1, Some(Duration::from_secs(2))), None);
+    }
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        assert_eq!(parse_retry_after(&retry_after(" 120 ")), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn parses_retry_after_http_date() {
+        let at = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let delay = parse_retry_after(&retry_after(&at)).unwrap();
+        assert!(delay > Duration::from_secs(80) && delay <= Duration::from_secs(90), "{:?}", delay);
+    }
+
+    #[test]
+    fn ignores_past_or_invalid_retry_after() {
+        let past = (Utc::now() - chrono::Duration::seconds(30)).to_rfc2822();
+        assert_eq!(parse_retry_after(&retry_after(&past)), None);
+        assert_eq!(parse_retry_after(&retry_after("soon")), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        let classify = |status| {
+            FetchError::from_status(status, &retry_after("3"), GatewayError::from_status(status, ""), "123456")
+        };
+        assert!(matches!(
+            classify(StatusCode::SERVICE_UNAVAILABLE),
+            FetchError::Retryable { retry_after: Some(delay), .. } if delay == Duration::from_secs(3)
+        ));
+        assert!(matches!(classify(StatusCode::TOO_MANY_REQUESTS), FetchError::Retryable { .. }));
+        assert!(matches!(classify(StatusCode::UNAUTHORIZED), FetchError::Unauthorized(_)));
+        assert!(matches!(classify(StatusCode::NOT_FOUND), FetchError::Permanent(_)));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::defaults::SatkerAttributes;
+use crate::ids::KdSatker;
+use crate::models::Rekening;
+use crate::quarantine::QuarantinedRecord;
+use anyhow::Result;
+
+/// What an upsert did to the account's row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    Inserted,
+    Updated,
+    /// The row already held the same values and was not touched.
+    Unchanged,
+}
+
+impl UpsertOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpsertOutcome::Inserted => "inserted",
+            UpsertOutcome::Updated => "updated",
+            UpsertOutcome::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// Upserts counted by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpsertCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl UpsertCounts {
+    pub fn record(&mut self, outcome: UpsertOutcome) {
+        match outcome {
+            UpsertOutcome::Inserted => self.inserted += 1,
+            UpsertOutcome::Updated => self.updated += 1,
+            UpsertOutcome::Unchanged => self.unchanged += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.inserted + self.updated + self.unchanged
+    }
+}
+
+impl std::ops::AddAssign for UpsertCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+    }
+}
+
+/// Result of one row of a bulk upsert; `Err` holds the database's message.
+pub type RowResult = std::result::Result<UpsertOutcome, String>;
+
+/// Storage the batch processor writes accounts into.
+///
+/// A `Transaction` is a connection with an open transaction. `checkpoint`
+/// commits what has been written so far and keeps the transaction open for
+/// the next records; `commit` and `rollback` end it. The batch processor
+/// shares the sink with the blocking threads that run its transactions.
+pub trait RekeningSink: Send + Sync + 'static {
+    type Transaction;
+
+    /// Up to `limit` active satkers in code order, after `after` if given,
+    /// so a run can page through them by the last code it got.
+    fn get_active_satkers(&self, after: Option<&KdSatker>, limit: i64) -> Result<Vec<KdSatker>>;
+
+    fn update_last_fetch_date(&self, kd_satker: &KdSatker) -> Result<()>;
+
+    /// OWNER, KODE_UNIT_TEKNIS and MATA_UANG overrides stored on the satker.
+    fn satker_attributes(&self, kd_satker: &KdSatker) -> Result<SatkerAttributes>;
+
+    fn begin_transaction(&self) -> Result<Self::Transaction>;
+
+    /// Inserts the account, or updates the existing row with the same NOREK
+    /// when any of its values differ.
+    fn upsert_rekening(&self, tx: &Self::Transaction, rekening: &Rekening) -> Result<UpsertOutcome>;
+
+    /// Upserts many accounts at once, returning one result per account in
+    /// order. Rows the database refuses are `Err` and the rest stay
+    /// written; `Err` for the call means the whole batch failed. The
+    /// default upserts one row at a time.
+    fn upsert_rekening_batch(&self, tx: &Self::Transaction, rekenings: &[Rekening]) -> Result<Vec<RowResult>> {
+        Ok(rekenings
+            .iter()
+            .map(|rekening| self.upsert_rekening(tx, rekening).map_err(|e| format!("{:#}", e)))
+            .collect())
+    }
+
+    /// Parks an account that cannot be loaded as is, keyed by NOREK, so
+    /// someone can look at it before it reaches V_BEN_REKONREK_SPRINT.
+    fn queue_for_review(&self, tx: &Self::Transaction, rekening: &Rekening, reason: &str) -> Result<()>;
+
+    /// Stores a record that could not be loaded, keyed by satker and
+    /// record hash. See `Quarantine` for the file fallback.
+    fn quarantine(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()>;
+
+    /// Quarantined records, oldest first, optionally for one satker.
+    fn list_quarantined(&self, kd_satker: Option<&KdSatker>) -> Result<Vec<QuarantinedRecord>>;
+
+    /// Removes a record from the quarantine once it has been loaded.
+    fn release_quarantined(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()>;
+
+    fn checkpoint(&self, tx: &Self::Transaction) -> Result<()>;
+
+    fn commit(&self, tx: &Self::Transaction) -> Result<()>;
+
+    fn rollback(&self, tx: &Self::Transaction) -> Result<()>;
+}
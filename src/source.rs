@@ -0,0 +1,118 @@
+use crate::gateway_error::GatewayError;
+use crate::ids::KdSatker;
+use crate::models::{RecordReject, RekeningData, RekeningResponse};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use log::info;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+/// What remains of a response once its records went down the channel.
+#[derive(Debug, Default)]
+pub struct StreamSummary {
+    pub success: bool,
+    pub message: String,
+    pub code: String,
+    pub length: i32,
+    /// Records sent down the channel.
+    pub records: usize,
+    pub rejects: Vec<RecordReject>,
+}
+
+impl StreamSummary {
+    /// Elements received, whether or not they deserialized.
+    pub fn received(&self) -> usize {
+        self.records + self.rejects.len()
+    }
+
+    pub fn gateway_error(&self) -> GatewayError {
+        GatewayError::from_code(&self.code, &self.message)
+    }
+
+    /// Adds a following page, renumbering its rejects to stay unique.
+    pub fn extend_page(&mut self, page: StreamSummary) {
+        let offset = self.received();
+        self.records += page.records;
+        self.rejects.extend(page.rejects.into_iter().map(|mut reject| {
+            reject.index += offset;
+            reject
+        }));
+    }
+}
+
+/// Sends the records of an already buffered response down the channel.
+pub async fn forward_response(
+    response: RekeningResponse,
+    records: &mpsc::Sender<RekeningData>,
+) -> Result<StreamSummary> {
+    let mut summary = StreamSummary {
+        success: response.success,
+        message: response.message,
+        code: response.code,
+        length: response.length,
+        records: 0,
+        rejects: response.rejects,
+    };
+
+    for record in response.data {
+        records
+            .send(record)
+            .await
+            .map_err(|_| anyhow!("Record consumer stopped"))?;
+        summary.records += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Anything that can hand back the accounts of one satker.
+#[async_trait]
+pub trait RekeningSource: Send + Sync {
+    async fn fetch_rekening_data(&self, kd_satker: &KdSatker) -> Result<RekeningResponse>;
+
+    /// Sends the satker's records down `records` as they become available
+    /// and returns everything else about the response. The default fetches
+    /// the whole response first.
+    async fn stream_rekening_data(
+        &self,
+        kd_satker: &KdSatker,
+        records: mpsc::Sender<RekeningData>,
+    ) -> Result<StreamSummary> {
+        let response = self.fetch_rekening_data(kd_satker).await?;
+        forward_response(response, &records).await
+    }
+
+    /// How long the source refuses calls after an outage, if it tracks that.
+    fn circuit_open_for(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Serves `<dir>/<kd_satker>.json` files holding gateway responses.
+pub struct FixtureSource {
+    dir: PathBuf,
+}
+
+impl FixtureSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl RekeningSource for FixtureSource {
+    async fn fetch_rekening_data(&self, kd_satker: &KdSatker) -> Result<RekeningResponse> {
+        let path = self.dir.join(format!("{}.json", kd_satker));
+        info!("Reading fixture for satker {} from {}", kd_satker, path.display());
+
+        let text = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("No fixture for satker {} at {}", kd_satker, path.display()))?;
+        let parsed = serde_json::from_str::<RekeningResponse>(&text)
+            .with_context(|| format!("Invalid fixture {}", path.display()))?;
+
+        info!("Loaded fixture for satker {}. Data count: {}", kd_satker, parsed.data.len());
+        Ok(parsed)
+    }
+}
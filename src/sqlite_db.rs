@@ -0,0 +1,536 @@
+use crate::columns::{ColumnMap, SqlValue};
+use crate::defaults::SatkerAttributes;
+use crate::ids::KdSatker;
+use crate::logging;
+use crate::models::Rekening;
+use crate::quarantine::QuarantinedRecord;
+use crate::sink::{RekeningSink, UpsertOutcome};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use log::{info, error};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{Null, ToSqlOutput, Type};
+use rusqlite::{params, params_from_iter, Connection};
+
+/// Local stand-in for the Oracle tables, so the pipeline runs without
+/// Oracle Instant Client. Creates the tables on first use.
+pub struct SqliteHandler {
+    pool: Pool<SqliteConnectionManager>,
+    columns: ColumnMap,
+    upsert_sql: String,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS V_BEN_REKONREK_SPRINT (
+        KODE TEXT,
+        KODE_SATKER TEXT,
+        NAMA_BANK TEXT,
+        NAMA_REK TEXT,
+        NO_IZIN TEXT,
+        NOREK TEXT NOT NULL UNIQUE,
+        TGL_IZIN TEXT,
+        OWNER TEXT,
+        KODE_UNIT_TEKNIS TEXT,
+        DESC_STATUS_REKENING TEXT,
+        STATUS_REKENING INTEGER,
+        MATA_UANG TEXT,
+        NAMA_JENIS TEXT,
+        KODE_BANK TEXT,
+        KODE_JENIS_BANK TEXT,
+        NAMA_CABANG TEXT,
+        KODE_STATUS TEXT,
+        ORIGINAL_VALUES TEXT,
+        CREATED_BY TEXT,
+        CREATED_DATE TEXT,
+        MODIFIED_BY TEXT,
+        MODIFIED_DATE TEXT,
+        VERSION INTEGER,
+        DELETED INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS V_BEN_REKONREK_REVIEW (
+        KODE_SATKER TEXT,
+        NOREK TEXT NOT NULL UNIQUE,
+        KDSTATUS TEXT,
+        NMSTATUS TEXT,
+        REASON TEXT,
+        CREATED_DATE TEXT,
+        MODIFIED_DATE TEXT
+    );
+    CREATE TABLE IF NOT EXISTS V_BEN_REKONREK_QUARANTINE (
+        KODE_SATKER TEXT NOT NULL,
+        RECORD_HASH TEXT NOT NULL,
+        RAW TEXT NOT NULL,
+        REASON TEXT,
+        RUN_ID TEXT,
+        QUARANTINED_AT TEXT,
+        PRIMARY KEY (KODE_SATKER, RECORD_HASH)
+    );
+    CREATE TABLE IF NOT EXISTS V_BEN_REKON_REK_SATKER (
+        kd_satker TEXT PRIMARY KEY,
+        is_active INTEGER NOT NULL DEFAULT 1,
+        last_fetch_date TEXT,
+        owner TEXT,
+        kode_unit_teknis TEXT,
+        mata_uang TEXT
+    );
+";
+
+/// Same semantics as the Oracle MERGE: NOREK is the match key, an update
+/// bumps VERSION and the audit columns, and a row whose values are all the
+/// same is left alone.
+fn upsert_sql(columns: &ColumnMap) -> String {
+    let columns = columns.columns().iter().enumerate();
+    let inserted = columns.clone().filter(|(_, c)| c.on_insert);
+    let insert = inserted.clone().map(|(_, c)| c.name.as_str()).collect::<Vec<_>>().join(", ");
+    let values = inserted
+        .map(|(i, _)| format!("?{}", i + 1))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Update-only columns are not part of the INSERT, so `excluded` does
+    // not have them; bind them directly
+    let update = columns
+        .clone()
+        .filter(|(_, c)| c.on_update)
+        .map(|(i, c)| {
+            if c.on_insert {
+                format!("{0} = excluded.{0}", c.name)
+            } else {
+                format!("{} = ?{}", c.name, i + 1)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",\n                ");
+    let changed = columns
+        .clone()
+        .filter(|(_, c)| c.detects_change())
+        .map(|(_, c)| format!("V_BEN_REKONREK_SPRINT.{0} IS NOT excluded.{0}", c.name))
+        .collect::<Vec<_>>()
+        .join("\n                OR ");
+
+    format!(
+        "INSERT INTO V_BEN_REKONREK_SPRINT (
+                {insert},
+                CREATED_DATE, VERSION, DELETED
+            ) VALUES (
+                {values},
+                CURRENT_TIMESTAMP, 1, 0
+            )
+            ON CONFLICT (NOREK) DO UPDATE SET
+                {update},
+                MODIFIED_DATE = CURRENT_TIMESTAMP,
+                VERSION = VERSION + 1
+            WHERE {changed}"
+    )
+}
+
+/// Matches what CURRENT_TIMESTAMP writes.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn sqlite_param<'a>(value: &SqlValue<'a>) -> ToSqlOutput<'a> {
+    match *value {
+        SqlValue::Text(text) => ToSqlOutput::from(text),
+        SqlValue::Integer(number) => ToSqlOutput::from(number),
+        SqlValue::Date(date) => date.format("%Y-%m-%d").to_string().into(),
+        SqlValue::Timestamp(timestamp) => timestamp.format(TIMESTAMP_FORMAT).to_string().into(),
+        SqlValue::Null(_) => ToSqlOutput::from(Null),
+    }
+}
+
+impl SqliteHandler {
+    pub fn new(path: &str, columns: ColumnMap) -> Result<Self> {
+        // Concurrent satkers share one file; wait for the write lock
+        // instead of failing with SQLITE_BUSY
+        let manager = SqliteConnectionManager::file(path)
+            .with_init(|conn| conn.busy_timeout(std::time::Duration::from_secs(30)));
+        let pool = Pool::new(manager)?;
+        let conn = pool.get()?;
+        conn.execute_batch(SCHEMA)?;
+
+        // SCHEMA only creates missing tables: databases from before a
+        // column was added lack it, and columns renamed through ColumnMap
+        // are not in SCHEMA at all
+        let names = columns.columns().iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+        add_missing_columns(&conn, "V_BEN_REKONREK_SPRINT", &names)?;
+        add_missing_columns(&conn, "V_BEN_REKON_REK_SATKER", &["owner", "kode_unit_teknis", "mata_uang"])?;
+        drop(conn);
+
+        let upsert_sql = upsert_sql(&columns);
+        Ok(Self { pool, columns, upsert_sql })
+    }
+}
+
+fn add_missing_columns(conn: &Connection, table: &str, columns: &[&str]) -> Result<()> {
+    let existing = conn
+        .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for column in columns {
+        if !existing.iter().any(|name| name.eq_ignore_ascii_case(column)) {
+            info!("Adding column {} to {}", column, table);
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} TEXT", table, column))?;
+        }
+    }
+    Ok(())
+}
+
+impl RekeningSink for SqliteHandler {
+    type Transaction = r2d2::PooledConnection<SqliteConnectionManager>;
+
+    fn get_active_satkers(&self, after: Option<&KdSatker>, limit: i64) -> Result<Vec<KdSatker>> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT kd_satker FROM V_BEN_REKON_REK_SATKER
+             WHERE is_active = 1
+               AND LENGTH(kd_satker) = 6
+               AND kd_satker NOT GLOB '*[^0-9]*'
+               AND (?1 IS NULL OR kd_satker > ?1)
+             ORDER BY kd_satker
+             LIMIT ?2",
+        )?;
+        let codes = stmt
+            .query_map(params![after, limit], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        // Same as the Oracle handler: invalid codes are filtered in the
+        // query, and anything still unparsable is logged and left out
+        let mut satkers = Vec::new();
+        for kd_satker in codes {
+            match kd_satker.parse::<KdSatker>() {
+                Ok(kd_satker) => satkers.push(kd_satker),
+                Err(e) => error!(event = "invalid_satker"; "Skipping satker {:?}: {}", kd_satker, e),
+            }
+        }
+
+        Ok(satkers)
+    }
+
+    fn update_last_fetch_date(&self, kd_satker: &KdSatker) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE V_BEN_REKON_REK_SATKER
+             SET last_fetch_date = CURRENT_TIMESTAMP
+             WHERE kd_satker = ?1",
+            [kd_satker],
+        )?;
+
+        Ok(())
+    }
+
+    fn satker_attributes(&self, kd_satker: &KdSatker) -> Result<SatkerAttributes> {
+        let conn = self.pool.get()?;
+        let attributes = conn.query_row(
+            "SELECT owner, kode_unit_teknis, mata_uang FROM V_BEN_REKON_REK_SATKER
+             WHERE kd_satker = ?1",
+            [kd_satker],
+            |row| {
+                Ok(SatkerAttributes {
+                    owner: row.get(0)?,
+                    kode_unit_teknis: row.get(1)?,
+                    mata_uang: row.get(2)?,
+                })
+            },
+        )?;
+
+        Ok(attributes)
+    }
+
+    fn begin_transaction(&self) -> Result<Self::Transaction> {
+        let conn = self.pool.get()?;
+        // A connection can come back to the pool mid-transaction if a
+        // satker bailed out early; start from a clean slate
+        if !conn.is_autocommit() {
+            conn.execute_batch("ROLLBACK")?;
+        }
+        conn.execute_batch("BEGIN IMMEDIATE")?;
+        info!("Transaction started successfully");
+        Ok(conn)
+    }
+
+    /// The transaction holds the write lock, so the NOREK looked up here
+    /// is still there, or still missing, when the upsert runs.
+    fn upsert_rekening(&self, tx: &Self::Transaction, rekening: &Rekening) -> Result<UpsertOutcome> {
+        info!("Inserting rekening in batch: {}", logging::norek(rekening.no_rekening.as_str()));
+
+        let exists = tx
+            .prepare_cached("SELECT 1 FROM V_BEN_REKONREK_SPRINT WHERE NOREK = ?1")?
+            .exists([&rekening.no_rekening])?;
+        let values = self.columns.values(rekening);
+        let mut stmt = tx.prepare_cached(&self.upsert_sql)?;
+        match stmt.execute(params_from_iter(values.iter().map(sqlite_param))) {
+            Ok(0) => Ok(UpsertOutcome::Unchanged),
+            Ok(_) if exists => Ok(UpsertOutcome::Updated),
+            Ok(_) => Ok(UpsertOutcome::Inserted),
+            Err(e) => {
+                error!(event = "upsert_failed"; "Error during batch insert for {}: {:?}", logging::norek(rekening.no_rekening.as_str()), e);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn queue_for_review(&self, tx: &Self::Transaction, rekening: &Rekening, reason: &str) -> Result<()> {
+        info!("Queueing rekening for review: {}", logging::norek(rekening.no_rekening.as_str()));
+        tx.execute(
+            "INSERT INTO V_BEN_REKONREK_REVIEW (
+                KODE_SATKER, NOREK, KDSTATUS, NMSTATUS, REASON, CREATED_DATE
+            ) VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)
+            ON CONFLICT (NOREK) DO UPDATE SET
+                KODE_SATKER = excluded.KODE_SATKER,
+                KDSTATUS = excluded.KDSTATUS,
+                NMSTATUS = excluded.NMSTATUS,
+                REASON = excluded.REASON,
+                MODIFIED_DATE = CURRENT_TIMESTAMP",
+            params![
+                rekening.kd_satker,
+                rekening.no_rekening,
+                rekening.kode_status,
+                rekening.desc_status_rekening,
+                reason,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn quarantine(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()> {
+        tx.execute(
+            "INSERT INTO V_BEN_REKONREK_QUARANTINE (
+                KODE_SATKER, RECORD_HASH, RAW, REASON, RUN_ID, QUARANTINED_AT
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (KODE_SATKER, RECORD_HASH) DO UPDATE SET
+                REASON = excluded.REASON,
+                RUN_ID = excluded.RUN_ID,
+                QUARANTINED_AT = excluded.QUARANTINED_AT",
+            params![
+                record.kd_satker,
+                record.record_hash,
+                record.raw,
+                record.reason,
+                record.run_id,
+                record.quarantined_at.format(TIMESTAMP_FORMAT).to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_quarantined(&self, kd_satker: Option<&KdSatker>) -> Result<Vec<QuarantinedRecord>> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT KODE_SATKER, RECORD_HASH, RAW, REASON, RUN_ID, QUARANTINED_AT
+             FROM V_BEN_REKONREK_QUARANTINE
+             WHERE ?1 IS NULL OR KODE_SATKER = ?1
+             ORDER BY QUARANTINED_AT",
+        )?;
+        let records = stmt
+            .query_map([kd_satker], |row| {
+                let quarantined_at = row.get::<_, String>(5)?;
+                Ok(QuarantinedRecord {
+                    kd_satker: row.get(0)?,
+                    record_hash: row.get(1)?,
+                    raw: row.get(2)?,
+                    reason: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    run_id: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                    quarantined_at: NaiveDateTime::parse_from_str(&quarantined_at, TIMESTAMP_FORMAT)
+                        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(e)))?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+
+    fn release_quarantined(&self, tx: &Self::Transaction, record: &QuarantinedRecord) -> Result<()> {
+        tx.execute(
+            "DELETE FROM V_BEN_REKONREK_QUARANTINE
+             WHERE KODE_SATKER = ?1 AND RECORD_HASH = ?2",
+            params![record.kd_satker, record.record_hash],
+        )?;
+        Ok(())
+    }
+
+    fn checkpoint(&self, tx: &Self::Transaction) -> Result<()> {
+        tx.execute_batch("COMMIT; BEGIN IMMEDIATE")?;
+        info!("Transaction committed successfully");
+        Ok(())
+    }
+
+    fn commit(&self, tx: &Self::Transaction) -> Result<()> {
+        tx.execute_batch("COMMIT")?;
+        info!("Transaction committed successfully");
+        Ok(())
+    }
+
+    fn rollback(&self, tx: &Self::Transaction) -> Result<()> {
+        // After a commit there is nothing left to roll back
+        if !tx.is_autocommit() {
+            tx.execute_batch("ROLLBACK")?;
+        }
+        info!("Transaction rolled back");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A handler on a fresh database file, removed on drop. `:memory:`
+    /// would give every pooled connection its own database.
+    struct Scratch {
+        path: PathBuf,
+        handler: SqliteHandler,
+    }
+
+    impl Scratch {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("gwsprint-{}.db", uuid::Uuid::new_v4()));
+            let handler = SqliteHandler::new(path.to_str().unwrap(), ColumnMap::from_env().unwrap()).unwrap();
+            Self { path, handler }
+        }
+
+        fn row(&self, norek: &str) -> (String, String, i64) {
+            self.handler
+                .pool
+                .get()
+                .unwrap()
+                .query_row(
+                    "SELECT KODE_SATKER, NAMA_BANK, VERSION FROM V_BEN_REKONREK_SPRINT WHERE NOREK = ?1",
+                    [norek],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn rekening(kd_satker: &str, norek: &str, nama_bank: &str) -> Rekening {
+        Rekening {
+            kdjenis: "1".to_string(),
+            kd_satker: kd_satker.parse().unwrap(),
+            nama_bank: nama_bank.to_string(),
+            nama_rekening: "BPP".to_string(),
+            no_izin: None,
+            no_rekening: norek.parse().unwrap(),
+            tgl_izin: None,
+            tgl_izin_timestamp: None,
+            tgl_izin_format: None,
+            desc_status_rekening: "Aktif".to_string(),
+            status_rekening: Some(1),
+            owner: String::new(),
+            kode_unit_teknis: String::new(),
+            mata_uang: "IDR".to_string(),
+            audit_user: "gwsprint".to_string(),
+            nama_jenis: String::new(),
+            kode_bank: String::new(),
+            kode_jenis_bank: String::new(),
+            nama_cabang: String::new(),
+            kode_status: "1".to_string(),
+            original_values: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn upsert(scratch: &Scratch, rekening: &Rekening) -> UpsertOutcome {
+        let tx = scratch.handler.begin_transaction().unwrap();
+        let outcome = scratch.handler.upsert_rekening(&tx, rekening).unwrap();
+        scratch.handler.commit(&tx).unwrap();
+        outcome
+    }
+
+    #[test]
+    fn new_norek_is_inserted_at_version_one() {
+        let scratch = Scratch::new();
+        assert_eq!(upsert(&scratch, &rekening("123456", "111", "BRI")), UpsertOutcome::Inserted);
+        assert_eq!(scratch.row("111"), ("123456".to_string(), "BRI".to_string(), 1));
+    }
+
+    #[test]
+    fn identical_row_is_left_unchanged() {
+        let scratch = Scratch::new();
+        upsert(&scratch, &rekening("123456", "111", "BRI"));
+        assert_eq!(upsert(&scratch, &rekening("123456", "111", "BRI")), UpsertOutcome::Unchanged);
+        assert_eq!(scratch.row("111").2, 1);
+    }
+
+    #[test]
+    fn changed_row_is_updated_and_bumps_version() {
+        let scratch = Scratch::new();
+        upsert(&scratch, &rekening("123456", "111", "BRI"));
+        assert_eq!(upsert(&scratch, &rekening("123456", "111", "BNI")), UpsertOutcome::Updated);
+        assert_eq!(scratch.row("111"), ("123456".to_string(), "BNI".to_string(), 2));
+    }
+
+    #[test]
+    fn update_keeps_insert_only_columns() {
+        let scratch = Scratch::new();
+        upsert(&scratch, &rekening("123456", "111", "BRI"));
+        // KODE_SATKER is insert-only, so it neither changes nor counts as a change
+        assert_eq!(upsert(&scratch, &rekening("654321", "111", "BRI")), UpsertOutcome::Unchanged);
+        assert_eq!(upsert(&scratch, &rekening("654321", "111", "BNI")), UpsertOutcome::Updated);
+        assert_eq!(scratch.row("111").0, "123456");
+    }
+
+    #[test]
+    fn rolled_back_upsert_leaves_nothing() {
+        let scratch = Scratch::new();
+        let tx = scratch.handler.begin_transaction().unwrap();
+        scratch.handler.upsert_rekening(&tx, &rekening("123456", "111", "BRI")).unwrap();
+        scratch.handler.rollback(&tx).unwrap();
+        drop(tx);
+
+        let tx = scratch.handler.begin_transaction().unwrap();
+        assert_eq!(scratch.handler.upsert_rekening(&tx, &rekening("123456", "111", "BRI")).unwrap(), UpsertOutcome::Inserted);
+    }
+
+    #[test]
+    fn batch_reports_each_row_in_order() {
+        let scratch = Scratch::new();
+        upsert(&scratch, &rekening("123456", "111", "BRI"));
+
+        let batch = [
+            rekening("123456", "111", "BRI"),
+            rekening("123456", "222", "BNI"),
+            rekening("123456", "111", "BNI"),
+        ];
+        let tx = scratch.handler.begin_transaction().unwrap();
+        let results = scratch.handler.upsert_rekening_batch(&tx, &batch).unwrap();
+        scratch.handler.commit(&tx).unwrap();
+
+        assert_eq!(
+            results,
+            [Ok(UpsertOutcome::Unchanged), Ok(UpsertOutcome::Inserted), Ok(UpsertOutcome::Updated)]
+        );
+        assert_eq!(scratch.row("111").1, "BNI");
+    }
+
+    #[test]
+    fn refused_row_fails_alone() {
+        let scratch = Scratch::new();
+        scratch
+            .handler
+            .pool
+            .get()
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER refuse_bad BEFORE INSERT ON V_BEN_REKONREK_SPRINT
+                 WHEN NEW.NAMA_BANK = 'BAD'
+                 BEGIN SELECT RAISE(ABORT, 'bank refused'); END;",
+            )
+            .unwrap();
+
+        let batch = [rekening("123456", "111", "BRI"), rekening("123456", "222", "BAD"), rekening("123456", "333", "BNI")];
+        let tx = scratch.handler.begin_transaction().unwrap();
+        let results = scratch.handler.upsert_rekening_batch(&tx, &batch).unwrap();
+        scratch.handler.commit(&tx).unwrap();
+
+        assert_eq!(results[0], Ok(UpsertOutcome::Inserted));
+        assert!(results[1].as_ref().unwrap_err().contains("bank refused"), "{:?}", results[1]);
+        assert_eq!(results[2], Ok(UpsertOutcome::Inserted));
+        assert_eq!(scratch.row("333").1, "BNI");
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::env;
+
+/// Used when STATUS_MAP is not set.
+const DEFAULT_STATUS_MAP: &str = "1=1,2=2,3=3";
+
+/// Gateway `kdstatus` to internal STATUS_REKENING code.
+#[derive(Debug, Clone)]
+pub struct StatusMap {
+    codes: HashMap<String, i64>,
+}
+
+impl StatusMap {
+    /// Reads STATUS_MAP as comma separated `kdstatus=code` pairs.
+    pub fn from_env() -> Result<Self> {
+        let spec = env::var("STATUS_MAP").unwrap_or_else(|_| DEFAULT_STATUS_MAP.to_string());
+        Self::parse(&spec)
+    }
+
+    fn parse(spec: &str) -> Result<Self> {
+        let mut codes = HashMap::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (kdstatus, code) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid STATUS_MAP entry {:?}, expected kdstatus=code", entry))?;
+            let code = code
+                .trim()
+                .parse::<i64>()
+                .map_err(|e| anyhow!("Invalid status code in STATUS_MAP entry {:?}: {}", entry, e))?;
+            if codes.insert(kdstatus.trim().to_string(), code).is_some() {
+                return Err(anyhow!("kdstatus {:?} is mapped twice in STATUS_MAP", kdstatus.trim()));
+            }
+        }
+
+        Ok(Self { codes })
+    }
+
+    /// `None` for a missing or unmapped kdstatus; such accounts go to review.
+    pub fn resolve(&self, kdstatus: Option<&str>) -> Option<i64> {
+        kdstatus.and_then(|kdstatus| self.codes.get(kdstatus.trim()).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pairs_and_resolves_trimmed_kdstatus() {
+        let map = StatusMap::parse(" A = 1 , B=2,, ").unwrap();
+        assert_eq!(map.resolve(Some("A")), Some(1));
+        assert_eq!(map.resolve(Some(" B ")), Some(2));
+        assert_eq!(map.resolve(Some("C")), None);
+        assert_eq!(map.resolve(None), None);
+    }
+
+    #[test]
+    fn default_map_is_identity() {
+        let map = StatusMap::parse(DEFAULT_STATUS_MAP).unwrap();
+        assert_eq!(map.resolve(Some("3")), Some(3));
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert!(StatusMap::parse("A").is_err());
+        assert!(StatusMap::parse("A=x").is_err());
+        assert!(StatusMap::parse("A=1,A=2").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use crate::models::{RecordReject, RekeningData};
+use crate::source::StreamSummary;
+use bytes::{Buf, Bytes};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+use std::fmt;
+use std::io::{self, BufReader, Read};
+use tokio::sync::mpsc;
+
+/// Blocking `Read` over body chunks sent from the async side.
+pub struct ChunkReader {
+    chunks: mpsc::Receiver<io::Result<Bytes>>,
+    current: Bytes,
+}
+
+impl ChunkReader {
+    pub fn new(chunks: mpsc::Receiver<io::Result<Bytes>>) -> Self {
+        Self {
+            chunks,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.current.has_remaining() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.current.remaining());
+        self.current.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Parses a gateway response from `reader`, sending each `data` element
+/// down `records` as soon as it is complete. Must run on a blocking thread.
+pub fn parse_response<R: Read>(
+    reader: R,
+    records: &mpsc::Sender<RekeningData>,
+) -> serde_json::Result<StreamSummary> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let summary = deserializer.deserialize_map(ResponseVisitor { records })?;
+    deserializer.end()?;
+    Ok(summary)
+}
+
+struct ResponseVisitor<'a> {
+    records: &'a mpsc::Sender<RekeningData>,
+}
+
+impl<'de> Visitor<'de> for ResponseVisitor<'_> {
+    type Value = StreamSummary;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a gateway response object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<StreamSummary, A::Error> {
+        let mut success = None;
+        let mut message = None;
+        let mut code = None;
+        let mut length = None;
+        let mut data = DataSummary::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "success" => success = Some(map.next_value()?),
+                "message" => message = Some(map.next_value()?),
+                "code" => code = Some(map.next_value()?),
+                "length" => length = Some(map.next_value()?),
+                "data" => data = map.next_value_seed(DataSeed { records: self.records })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(StreamSummary {
+            success: success.ok_or_else(|| de::Error::missing_field("success"))?,
+            message: message.ok_or_else(|| de::Error::missing_field("message"))?,
+            code: code.ok_or_else(|| de::Error::missing_field("code"))?,
+            length: length.ok_or_else(|| de::Error::missing_field("length"))?,
+            records: data.records,
+            rejects: data.rejects,
+        })
+    }
+}
+
+#[derive(Default)]
+struct DataSummary {
+    records: usize,
+    rejects: Vec<RecordReject>,
+}
+
+struct DataSeed<'a> {
+    records: &'a mpsc::Sender<RekeningData>,
+}
+
+impl<'de> DeserializeSeed<'de> for DataSeed<'_> {
+    type Value = DataSummary;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<DataSummary, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataSeed<'_> {
+    type Value = DataSummary;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of records or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<DataSummary, E> {
+        Ok(DataSummary::default())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<DataSummary, E> {
+        Ok(DataSummary::default())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<DataSummary, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    // Same per-element tolerance as the buffered path
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<DataSummary, A::Error> {
+        let mut summary = DataSummary::default();
+        let mut index = 0;
+
+        while let Some(element) = seq.next_element::<serde_json::Value>()? {
+            let raw = element.to_string();
+            match serde_json::from_value::<RekeningData>(element) {
+                Ok(record) => {
+                    self.records
+                        .blocking_send(record)
+                        .map_err(|_| de::Error::custom("record consumer stopped"))?;
+                    summary.records += 1;
+                }
+                Err(e) => summary.rejects.push(RecordReject {
+                    index,
+                    raw,
+                    error: e.to_string(),
+                }),
+            }
+            index += 1;
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> (serde_json::Result<StreamSummary>, Vec<RekeningData>) {
+        let (tx, mut rx) = mpsc::channel(16);
+        let summary = parse_response(body.as_bytes(), &tx);
+        drop(tx);
+        let mut records = Vec::new();
+        while let Ok(record) = rx.try_recv() {
+            records.push(record);
+        }
+        (summary, records)
+    }
+
+    #[test]
+    fn streams_records_and_collects_rejects() {
+        let body = r#"{"success":true,"message":"OK","code":"00","length":3,"extra":[1,2],
+            "data":[{"kdsatker":"123456","norek":"1"},{"norek":42},{"kdsatker":"123456","norek":"3"}]}"#;
+        let (summary, records) = parse(body);
+        let summary = summary.unwrap();
+
+        assert_eq!(summary.records, 2);
+        assert_eq!(summary.received(), 3);
+        assert_eq!(summary.rejects.len(), 1);
+        assert_eq!(summary.rejects[0].index, 1);
+        assert_eq!(summary.rejects[0].raw, r#"{"norek":42}"#);
+        let norek = records.iter().map(|record| record.norek.as_deref()).collect::<Vec<_>>();
+        assert_eq!(norek, [Some("1"), Some("3")]);
+    }
+
+    #[test]
+    fn null_or_missing_data_is_empty() {
+        for body in [
+            r#"{"success":false,"message":"none","code":"404","length":0,"data":null}"#,
+            r#"{"success":false,"message":"none","code":"404","length":0}"#,
+        ] {
+            let (summary, records) = parse(body);
+            let summary = summary.unwrap();
+            assert_eq!(summary.records, 0);
+            assert!(summary.rejects.is_empty());
+            assert!(records.is_empty());
+        }
+    }
+
+    #[test]
+    fn missing_envelope_field_is_an_error() {
+        let (summary, _) = parse(r#"{"success":true,"message":"OK","code":"00","data":[]}"#);
+        assert!(summary.unwrap_err().to_string().contains("length"));
+    }
+
+    #[test]
+    fn truncated_body_is_an_error() {
+        let (summary, records) = parse(r#"{"success":true,"message":"OK","code":"00","length":2,"data":[{"norek":"1"},"#);
+        assert!(summary.is_err());
+        assert_eq!(records.len(), 1);
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::pkcs12::Pkcs12;
+use reqwest::tls::{Certificate, Identity};
+use reqwest::ClientBuilder;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{CertificateError, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// TLS settings for the gateway connection on top of the system trust store.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    pub ca_file: Option<PathBuf>,
+    pub client_identity: Option<ClientIdentity>,
+    /// SHA-256 digests of the SubjectPublicKeyInfo the server certificates
+    /// may carry. Empty for no pinning.
+    pub spki_pins: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClientIdentity {
+    /// PEM certificate (chain) plus a PKCS#8 PEM private key.
+    Pem { cert: PathBuf, key: PathBuf },
+    Pkcs12 { path: PathBuf, password: String },
+}
+
+impl TlsConfig {
+    /// Reads GATEWAY_CA_FILE, GATEWAY_CLIENT_CERT, GATEWAY_CLIENT_KEY,
+    /// GATEWAY_CLIENT_P12_PASSWORD and GATEWAY_SPKI_PIN. A client certificate
+    /// ending in `.p12` or `.pfx` is loaded as PKCS#12, anything else as PEM.
+    /// GATEWAY_SPKI_PIN takes a comma separated list, so the token endpoint's
+    /// key can be pinned next to the gateway's.
+    pub fn from_env() -> Result<Self> {
+        let client_identity = match env::var("GATEWAY_CLIENT_CERT") {
+            Ok(cert) => {
+                let cert = PathBuf::from(cert);
+                let is_pkcs12 = cert
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| ext.eq_ignore_ascii_case("p12") || ext.eq_ignore_ascii_case("pfx"))
+                    .unwrap_or(false);
+
+                if is_pkcs12 {
+                    Some(ClientIdentity::Pkcs12 {
+                        path: cert,
+                        password: env::var("GATEWAY_CLIENT_P12_PASSWORD").unwrap_or_default(),
+                    })
+                } else {
+                    let key = env::var("GATEWAY_CLIENT_KEY")
+                        .context("GATEWAY_CLIENT_KEY is required for a PEM client certificate")?;
+                    Some(ClientIdentity::Pem { cert, key: PathBuf::from(key) })
+                }
+            }
+            Err(_) => None,
+        };
+
+        let spki_pins = env::var("GATEWAY_SPKI_PIN")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|pin| !pin.trim().is_empty())
+            .map(parse_pin)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            ca_file: env::var("GATEWAY_CA_FILE").ok().map(PathBuf::from),
+            client_identity,
+            spki_pins,
+        })
+    }
+
+    /// Loads the configured files into the client builder. Errors name the
+    /// offending file so a bad deployment fails at startup.
+    ///
+    /// With a pin the connection goes through rustls, whose verifier checks
+    /// the pin during the handshake: a mismatching server is dropped before
+    /// any request, token or client secret is sent to it.
+    pub fn apply(&self, mut builder: ClientBuilder, offer_h2: bool) -> Result<ClientBuilder> {
+        if !self.spki_pins.is_empty() {
+            return Ok(builder.use_preconfigured_tls(self.pinned_config(offer_h2)?));
+        }
+
+        if let Some(ca_file) = &self.ca_file {
+            let pem = fs::read(ca_file)
+                .with_context(|| format!("Cannot read CA bundle {}", ca_file.display()))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA bundle {}", ca_file.display()))?;
+            if certs.is_empty() {
+                return Err(anyhow!("CA bundle {} contains no certificates", ca_file.display()));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match &self.client_identity {
+            Some(ClientIdentity::Pem { cert, key }) => {
+                let cert_pem = fs::read(cert)
+                    .with_context(|| format!("Cannot read client certificate {}", cert.display()))?;
+                let key_pem = fs::read(key)
+                    .with_context(|| format!("Cannot read client key {}", key.display()))?;
+                let identity = Identity::from_pkcs8_pem(&cert_pem, &key_pem).with_context(|| {
+                    format!("Invalid client identity {} / {}", cert.display(), key.display())
+                })?;
+                builder = builder.identity(identity);
+            }
+            Some(ClientIdentity::Pkcs12 { path, password }) => {
+                let der = fs::read(path)
+                    .with_context(|| format!("Cannot read PKCS#12 file {}", path.display()))?;
+                let identity = Identity::from_pkcs12_der(&der, password)
+                    .with_context(|| format!("Invalid PKCS#12 file {}", path.display()))?;
+                builder = builder.identity(identity);
+            }
+            None => {}
+        }
+
+        Ok(builder)
+    }
+
+    /// The same trust store and client identity as the native-tls setup,
+    /// with the pin checked on top of the usual chain and hostname checks.
+    fn pinned_config(&self, offer_h2: bool) -> Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        let system = rustls_native_certs::load_native_certs().context("Cannot load system trust store")?;
+        // Like native-tls, skip system certificates that do not parse
+        roots.add_parsable_certificates(&system.into_iter().map(|cert| cert.0).collect::<Vec<_>>());
+
+        if let Some(ca_file) = &self.ca_file {
+            let pem = fs::read(ca_file)
+                .with_context(|| format!("Cannot read CA bundle {}", ca_file.display()))?;
+            let certs = rustls_pemfile::certs(&mut pem.as_slice())
+                .with_context(|| format!("Invalid CA bundle {}", ca_file.display()))?;
+            if certs.is_empty() {
+                return Err(anyhow!("CA bundle {} contains no certificates", ca_file.display()));
+            }
+            let (_, invalid) = roots.add_parsable_certificates(&certs);
+            if invalid > 0 {
+                return Err(anyhow!("Invalid CA bundle {}", ca_file.display()));
+            }
+        }
+
+        let verifier = PinnedVerifier {
+            inner: WebPkiVerifier::new(roots, None),
+            pins: self.spki_pins.clone(),
+        };
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        let mut config = match &self.client_identity {
+            Some(ClientIdentity::Pem { cert, key }) => {
+                let cert_pem = fs::read(cert)
+                    .with_context(|| format!("Cannot read client certificate {}", cert.display()))?;
+                let key_pem = fs::read(key)
+                    .with_context(|| format!("Cannot read client key {}", key.display()))?;
+                let chain = rustls_pemfile::certs(&mut cert_pem.as_slice())
+                    .with_context(|| format!("Invalid client certificate {}", cert.display()))?;
+                let key_der = rustls_pemfile::pkcs8_private_keys(&mut key_pem.as_slice())
+                    .ok()
+                    .and_then(|keys| keys.into_iter().next())
+                    .ok_or_else(|| anyhow!("No PKCS#8 private key in {}", key.display()))?;
+                builder
+                    .with_client_auth_cert(chain.into_iter().map(rustls::Certificate).collect(), PrivateKey(key_der))
+                    .with_context(|| format!("Invalid client identity {} / {}", cert.display(), key.display()))?
+            }
+            Some(ClientIdentity::Pkcs12 { path, password }) => {
+                let der = fs::read(path)
+                    .with_context(|| format!("Cannot read PKCS#12 file {}", path.display()))?;
+                let (chain, key_der) = pkcs12_identity(&der, password)
+                    .with_context(|| format!("Invalid PKCS#12 file {}", path.display()))?;
+                builder
+                    .with_client_auth_cert(chain, PrivateKey(key_der))
+                    .with_context(|| format!("Invalid PKCS#12 file {}", path.display()))?
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        config.alpn_protocols = if offer_h2 {
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        } else {
+            vec![b"http/1.1".to_vec()]
+        };
+        Ok(config)
+    }
+}
+
+/// Certificate chain and PKCS#8 key of a PKCS#12 file, leaf first.
+fn pkcs12_identity(der: &[u8], password: &str) -> Result<(Vec<rustls::Certificate>, Vec<u8>)> {
+    let parsed = Pkcs12::from_der(der)?.parse2(password)?;
+    let key = parsed.pkey.ok_or_else(|| anyhow!("no private key"))?;
+    let leaf = parsed.cert.ok_or_else(|| anyhow!("no certificate"))?;
+
+    let mut chain = vec![rustls::Certificate(leaf.to_der()?)];
+    for cert in parsed.ca.iter().flatten() {
+        chain.push(rustls::Certificate(cert.to_der()?));
+    }
+    Ok((chain, key.private_key_to_pkcs8()?))
+}
+
+/// Raised from the handshake when the server key is not pinned.
+pub struct PinMismatch {
+    digest: Vec<u8>,
+}
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Server certificate does not match SPKI pin (got sha256/{})", STANDARD.encode(&self.digest))
+    }
+}
+
+// rustls shows certificate errors with Debug
+impl fmt::Debug for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for PinMismatch {}
+
+impl PinMismatch {
+    /// Whether `error` was caused by a pin mismatch, looking through the
+    /// I/O and rustls errors reqwest wraps it in.
+    pub fn caused(error: &(dyn Error + 'static)) -> bool {
+        let mut next = Some(error);
+        while let Some(error) = next {
+            if error.is::<PinMismatch>() {
+                return true;
+            }
+            // io::Error::source skips the error it wraps
+            if let Some(inner) = error.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+                next = Some(inner);
+                continue;
+            }
+            if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(inner))) =
+                error.downcast_ref::<rustls::Error>()
+            {
+                next = Some(inner.as_ref());
+                continue;
+            }
+            next = error.source();
+        }
+        false
+    }
+}
+
+struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pins: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+
+        let (_, cert) = X509Certificate::from_der(&end_entity.0)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        let digest = Sha256::digest(cert.public_key().raw);
+        if !self.pins.iter().any(|pin| pin.as_slice() == digest.as_slice()) {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(Arc::new(PinMismatch {
+                digest: digest.to_vec(),
+            }))));
+        }
+        Ok(verified)
+    }
+}
+
+/// Accepts `sha256/<base64>` or the bare base64 digest.
+fn parse_pin(pin: &str) -> Result<Vec<u8>> {
+    let encoded = pin.trim().trim_start_matches("sha256/");
+    let digest = STANDARD
+        .decode(encoded)
+        .context("GATEWAY_SPKI_PIN must be a base64 SHA-256 digest")?;
+    if digest.len() != 32 {
+        return Err(anyhow!("GATEWAY_SPKI_PIN must decode to 32 bytes, got {}", digest.len()));
+    }
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_accepts_prefixed_and_bare_digests() {
+        let encoded = STANDARD.encode([7u8; 32]);
+        assert_eq!(parse_pin(&format!("sha256/{}", encoded)).unwrap(), vec![7u8; 32]);
+        assert_eq!(parse_pin(&format!("  {} ", encoded)).unwrap(), vec![7u8; 32]);
+    }
+
+    #[test]
+    fn pin_must_be_base64() {
+        assert!(parse_pin("sha256/not base64!").is_err());
+    }
+
+    #[test]
+    fn pin_must_be_a_sha256_digest() {
+        assert!(parse_pin(&STANDARD.encode([7u8; 20])).is_err());
+    }
+
+    #[test]
+    fn pin_mismatch_is_found_through_io_and_rustls_wrappers() {
+        let mismatch = Arc::new(PinMismatch { digest: vec![0; 32] });
+        let rustls_error = rustls::Error::InvalidCertificate(CertificateError::Other(mismatch));
+        let error = io::Error::new(io::ErrorKind::InvalidData, rustls_error);
+
+        assert!(PinMismatch::caused(&error));
+        assert!(!PinMismatch::caused(&io::Error::other("connection reset")));
+    }
+}
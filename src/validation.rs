@@ -0,0 +1,212 @@
+use crate::dates::DateConfig;
+use crate::normalize::Normalization;
+use crate::status_map::StatusMap;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+/// Used when VALIDATION_MAX_LENGTHS is not set.
+const DEFAULT_MAX_LENGTHS: &str = "norek=50,nmrek=255,nmbank=255,noizin=100,nmcabang=255";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Reported, but the record is still loaded.
+    Warning,
+    /// The record is not loaded.
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// One rule a gateway record broke. Messages leave out account numbers
+/// and names, so issues can be logged without masking.
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    /// Gateway field name, e.g. `norek`.
+    pub field: &'static str,
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn error(field: &'static str, rule: &'static str, message: impl Into<String>) -> Self {
+        Self { field, rule, severity: Severity::Error, message: message.into() }
+    }
+
+    pub fn warning(field: &'static str, rule: &'static str, message: impl Into<String>) -> Self {
+        Self { field, rule, severity: Severity::Warning, message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// `field.rule`, for run summaries.
+    pub fn key(&self) -> String {
+        format!("{}.{}", self.field, self.rule)
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}: {}", self.severity.as_str(), self.field, self.rule, self.message)
+    }
+}
+
+/// Column size limits for gateway text fields.
+#[derive(Debug, Clone)]
+pub struct ValidationRules {
+    max_lengths: BTreeMap<String, usize>,
+}
+
+impl ValidationRules {
+    /// Reads VALIDATION_MAX_LENGTHS as comma separated `field=bytes` pairs,
+    /// keyed by gateway field name.
+    pub fn from_env() -> Result<Self> {
+        Self::parse(&env::var("VALIDATION_MAX_LENGTHS").unwrap_or_else(|_| DEFAULT_MAX_LENGTHS.to_string()))
+    }
+
+    fn parse(spec: &str) -> Result<Self> {
+        let mut max_lengths = BTreeMap::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (field, max) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid VALIDATION_MAX_LENGTHS entry {:?}, expected field=bytes", entry))?;
+            let max = max
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| anyhow!("Invalid length in VALIDATION_MAX_LENGTHS entry {:?}: {}", entry, e))?;
+            max_lengths.insert(field.trim().to_lowercase(), max);
+        }
+
+        Ok(Self { max_lengths })
+    }
+
+    /// Oracle VARCHAR2 columns count bytes by default, so this does too.
+    pub fn check_length(&self, field: &'static str, value: Option<&str>, issues: &mut Vec<ValidationIssue>) {
+        let (Some(value), Some(max)) = (value, self.max_lengths.get(field)) else {
+            return;
+        };
+        if value.len() > *max {
+            issues.push(ValidationIssue::error(
+                field,
+                "length",
+                format!("{} is {} bytes, the column holds {}", field, value.len(), max),
+            ));
+        }
+    }
+}
+
+/// Everything `to_rekening` checks a record against, apart from the
+/// per-satker column defaults.
+#[derive(Debug, Clone)]
+pub struct RecordRules {
+    pub normalization: Normalization,
+    pub statuses: StatusMap,
+    pub dates: DateConfig,
+    pub validation: ValidationRules,
+}
+
+impl RecordRules {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            normalization: Normalization::from_env()?,
+            statuses: StatusMap::from_env()?,
+            dates: DateConfig::from_env()?,
+            validation: ValidationRules::from_env()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defaults::RecordDefaults;
+    use crate::models::RekeningData;
+
+    fn issues(rules: &ValidationRules, field: &'static str, value: &str) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        rules.check_length(field, Some(value), &mut issues);
+        issues
+    }
+
+    #[test]
+    fn length_limit_counts_bytes() {
+        let rules = ValidationRules::parse("nmrek=4").unwrap();
+        assert!(issues(&rules, "nmrek", "abcd").is_empty());
+        // Two characters, but four bytes and then six
+        assert!(issues(&rules, "nmrek", "éé").is_empty());
+        let too_long = issues(&rules, "nmrek", "ééé");
+        assert_eq!(too_long.len(), 1);
+        assert!(too_long[0].is_error());
+        assert_eq!(too_long[0].key(), "nmrek.length");
+    }
+
+    #[test]
+    fn fields_without_a_limit_are_not_checked() {
+        let rules = ValidationRules::parse("nmrek=4").unwrap();
+        assert!(issues(&rules, "nmbank", "a much longer bank name").is_empty());
+    }
+
+    #[test]
+    fn limit_spec_is_lenient_about_case_and_spacing_but_not_syntax() {
+        let rules = ValidationRules::parse(" NMREK = 10 , norek=3,").unwrap();
+        assert_eq!(rules.max_lengths.get("nmrek"), Some(&10));
+        assert!(ValidationRules::parse("nmrek").is_err());
+        assert!(ValidationRules::parse("nmrek=ten").is_err());
+    }
+
+    #[test]
+    fn default_limits_parse() {
+        assert_eq!(ValidationRules::parse(DEFAULT_MAX_LENGTHS).unwrap().max_lengths.len(), 5);
+    }
+
+    fn record(tglizin: &str, nmrek: &str) -> RekeningData {
+        RekeningData {
+            kdsatker: Some("123456".to_string()),
+            norek: Some("111".to_string()),
+            nmrek: Some(nmrek.to_string()),
+            tglizin: Some(tglizin.to_string()),
+            kdstatus: Some("1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn defaults() -> RecordDefaults {
+        RecordDefaults {
+            owner: "1".to_string(),
+            kode_unit_teknis: String::new(),
+            mata_uang: "IDR".to_string(),
+            audit_user: "SYSTEM".to_string(),
+        }
+    }
+
+    #[test]
+    fn warnings_pass_and_travel_with_the_record() {
+        let rules = RecordRules::from_env().unwrap();
+        let rekening = record("2999-01-01", "BPP").to_rekening(&rules, &defaults()).unwrap();
+        assert_eq!(rekening.warnings.len(), 1);
+        assert_eq!(rekening.warnings[0].severity, Severity::Warning);
+        assert_eq!(rekening.warnings[0].key(), "tglizin.not_future");
+    }
+
+    #[test]
+    fn errors_block_the_record() {
+        let mut rules = RecordRules::from_env().unwrap();
+        rules.validation = ValidationRules::parse("nmrek=3").unwrap();
+        let issues = record("2999-01-01", "BPP Satu").to_rekening(&rules, &defaults()).err().unwrap();
+        assert!(issues.iter().any(|issue| issue.is_error() && issue.key() == "nmrek.length"));
+        // The warning is reported alongside the error
+        assert!(issues.iter().any(|issue| !issue.is_error()));
+    }
+}
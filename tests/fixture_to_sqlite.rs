@@ -0,0 +1,180 @@
+//! Runs the whole pipeline from fixture files into a scratch SQLite
+//! database, with every setting at its default.
+
+use anyhow::Result;
+use gwsprint::batch_processor::{BatchProcessor, CircuitOpenAction, RunSummary};
+use gwsprint::columns::ColumnMap;
+use gwsprint::defaults::ColumnDefaults;
+use gwsprint::quarantine::Quarantine;
+use gwsprint::sink::UpsertCounts;
+use gwsprint::source::FixtureSource;
+use gwsprint::sqlite_db::SqliteHandler;
+use gwsprint::validation::RecordRules;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FIXTURE: &str = r#"{"success":true,"message":"ok","code":"00","length":4,"data":[
+    {"kdjenis":"1","kdsatker":"123456","nmbank":"BANK  BRI ","nmrek":"BPP Satu","norek":"0012-3456","noizin":"S-1","tglizin":"2023-01-05T17:30:00.000Z","kdstatus":"1"},
+    {"kdjenis":"1","kdsatker":"123456","nmbank":"BNI","nmrek":"BPP Dua","norek":"7654321","noizin":"S-2","tglizin":"2023-02-01","kdstatus":"1"},
+    {"kdjenis":"1","kdsatker":"123456","nmbank":"BNI","nmrek":"BPP Tiga","norek":42},
+    {"kdjenis":"1","kdsatker":"123456","nmbank":"BNI","nmrek":"BPP Empat","norek":"1111","noizin":"S-4","tglizin":"kemarin","kdstatus":"1"}
+]}"#;
+
+/// A fresh directory under the system temp dir, removed on drop.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("gwsprint-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+async fn run(fixtures: &Path, db_path: &str, concurrency: usize) -> Result<RunSummary> {
+    let sink = SqliteHandler::new(db_path, ColumnMap::from_env()?)?;
+    let processor = BatchProcessor::new(
+        FixtureSource::new(fixtures),
+        sink,
+        concurrency,
+        CircuitOpenAction::Abort,
+        RecordRules::from_env()?,
+        ColumnDefaults::from_env()?,
+        Quarantine::from_env(),
+    );
+    processor.process_all_satkers().await
+}
+
+fn count(conn: &Connection, sql: &str) -> i64 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[tokio::test]
+async fn loads_fixture_into_sqlite() -> Result<()> {
+    let scratch = Scratch::new();
+    fs::write(scratch.path().join("123456.json"), FIXTURE)?;
+    // Nothing for this satker passes validation
+    fs::write(
+        scratch.path().join("222222.json"),
+        r#"{"success":true,"message":"ok","code":"00","length":1,"data":[
+            {"kdjenis":"1","kdsatker":"222222","nmbank":"BNI","nmrek":"BPP","norek":"2222","noizin":"S-1","kdstatus":"1"}]}"#,
+    )?;
+    let db_path = scratch.path().join("gwsprint.db");
+    let db_path = db_path.to_str().unwrap();
+
+    // Creates the schema, then registers the satkers: two active, one
+    // inactive and one with an invalid code, neither of which has a fixture
+    SqliteHandler::new(db_path, ColumnMap::from_env()?)?;
+    let conn = Connection::open(db_path)?;
+    conn.execute_batch(
+        "INSERT INTO V_BEN_REKON_REK_SATKER (kd_satker, is_active) VALUES
+            ('123456', 1), ('222222', 1), ('654321', 0), ('12345X', 1);",
+    )?;
+
+    let summary = run(scratch.path(), db_path, 1).await?;
+    assert_eq!(summary.loaded_satkers, 1);
+    assert_eq!(
+        summary.outcomes.into_iter().collect::<Vec<_>>(),
+        [("loaded".to_string(), 1), ("quarantined".to_string(), 1)]
+    );
+    assert_eq!(summary.counts, UpsertCounts { inserted: 2, updated: 0, unchanged: 0 });
+
+    let rows = conn
+        .prepare("SELECT NOREK, KODE_SATKER, NAMA_BANK, VERSION FROM V_BEN_REKONREK_SPRINT ORDER BY NOREK")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String, String, i64)>>>()?;
+    assert_eq!(
+        rows,
+        [
+            ("00123456".to_string(), "123456".to_string(), "BANK BRI".to_string(), 1),
+            ("7654321".to_string(), "123456".to_string(), "BNI".to_string(), 1),
+        ]
+    );
+    let tgl_izin: String = conn.query_row(
+        "SELECT TGL_IZIN FROM V_BEN_REKONREK_SPRINT WHERE NOREK = '00123456'",
+        [],
+        |row| row.get(0),
+    )?;
+    assert!(tgl_izin.starts_with("2023-01-06"), "{}", tgl_izin);
+
+    // The element that does not deserialize and the one with an unparsable
+    // date are kept for review instead of being dropped
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_QUARANTINE WHERE KODE_SATKER = '123456'"), 2);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_QUARANTINE WHERE KODE_SATKER = '222222'"), 1);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKON_REK_SATKER WHERE last_fetch_date IS NOT NULL"), 2);
+
+    // A second run over the same data changes nothing
+    let summary = run(scratch.path(), db_path, 1).await?;
+    assert_eq!(summary.counts, UpsertCounts { inserted: 0, updated: 0, unchanged: 2 });
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT"), 2);
+    assert_eq!(count(&conn, "SELECT MAX(VERSION) FROM V_BEN_REKONREK_SPRINT"), 1);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_QUARANTINE"), 3);
+
+    Ok(())
+}
+
+/// A fixture of `records` valid accounts for the satker.
+fn fixture(kd_satker: &str, records: usize) -> String {
+    let data = (0..records)
+        .map(|i| {
+            format!(
+                r#"{{"kdjenis":"1","kdsatker":"{0}","nmbank":"BNI","nmrek":"BPP {1}","norek":"{0}{1:04}","noizin":"S-{1}","tglizin":"2023-02-01","kdstatus":"1"}}"#,
+                kd_satker, i
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#"{{"success":true,"message":"ok","code":"00","length":{},"data":[{}]}}"#, records, data)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_satkers_load_every_row() -> Result<()> {
+    let scratch = Scratch::new();
+    let satkers = ["100001", "100002", "100003", "100004", "100005", "100006"];
+    // More than one transaction batch per satker
+    let records = 120;
+    for kd_satker in satkers {
+        fs::write(scratch.path().join(format!("{}.json", kd_satker)), fixture(kd_satker, records))?;
+    }
+    let db_path = scratch.path().join("gwsprint.db");
+    let db_path = db_path.to_str().unwrap();
+
+    SqliteHandler::new(db_path, ColumnMap::from_env()?)?;
+    let conn = Connection::open(db_path)?;
+    for kd_satker in satkers {
+        conn.execute("INSERT INTO V_BEN_REKON_REK_SATKER (kd_satker, is_active) VALUES (?1, 1)", [kd_satker])?;
+    }
+
+    let summary = run(scratch.path(), db_path, 4).await?;
+    assert_eq!(summary.loaded_satkers, satkers.len());
+    assert_eq!(summary.counts.inserted, satkers.len() * records);
+
+    let expected = (satkers.len() * records) as i64;
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT"), expected);
+    for kd_satker in satkers {
+        let loaded: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM V_BEN_REKONREK_SPRINT WHERE KODE_SATKER = ?1",
+            [kd_satker],
+            |row| row.get(0),
+        )?;
+        assert_eq!(loaded, records as i64, "satker {}", kd_satker);
+    }
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM V_BEN_REKONREK_QUARANTINE"), 0);
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM V_BEN_REKON_REK_SATKER WHERE last_fetch_date IS NOT NULL"),
+        satkers.len() as i64
+    );
+
+    Ok(())
+}